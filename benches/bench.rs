@@ -1,5 +1,4 @@
 #![feature(test)]
-#![feature(iterator_step_by)]
 extern crate unicode_normalization;
 extern crate test;
 
@@ -7,65 +6,82 @@ use std::fs;
 use test::Bencher;
 use unicode_normalization::UnicodeNormalization;
 
-const ASCII: &'static str = "all types of normalized";
-const NFC: &'static str = "Introducci\u{00f3}n a Unicode.pdf";
-const NFD: &'static str = "Introduccio\u{0301}n a Unicode.pdf";
+const ASCII: &str = "all types of normalized";
+#[cfg(feature = "alloc")]
+const NFC: &str = "Introducci\u{00f3}n a Unicode.pdf";
+#[cfg(feature = "alloc")]
+const NFD: &str = "Introduccio\u{0301}n a Unicode.pdf";
+// Text outside Latin-1, where every character needs a table lookup.
+const MIXED: &str = "Ti\u{1ebf}ng Vi\u{1ec7}t \u{3b5}\u{3bb}\u{3bb}\u{3b7}\u{3bd}\u{3b9}\u{3ba}\u{3ac} \
+    \u{d55c}\u{ad6d}\u{c5b4} \u{65e5}\u{672c}\u{8a9e} \u{444}\u{430}\u{439}\u{43b} \u{fb01}\u{2075} ";
 
+#[cfg(feature = "alloc")]
 #[bench]
 fn bench_is_nfc_ascii(b: &mut Bencher) {
     b.iter(|| unicode_normalization::is_nfc(ASCII));
 }
 
+#[cfg(feature = "alloc")]
 #[bench]
 fn bench_is_nfc_normalized(b: &mut Bencher) {
     b.iter(|| unicode_normalization::is_nfc(NFC));
 }
 
+#[cfg(feature = "alloc")]
 #[bench]
 fn bench_is_nfc_not_normalized(b: &mut Bencher) {
     b.iter(|| unicode_normalization::is_nfc(NFD));
 }
 
+#[cfg(feature = "alloc")]
 #[bench]
 fn bench_is_nfd_ascii(b: &mut Bencher) {
     b.iter(|| unicode_normalization::is_nfd(ASCII));
 }
 
+#[cfg(feature = "alloc")]
 #[bench]
 fn bench_is_nfd_normalized(b: &mut Bencher) {
     b.iter(|| unicode_normalization::is_nfd(NFD));
 }
 
+#[cfg(feature = "alloc")]
 #[bench]
 fn bench_is_nfd_not_normalized(b: &mut Bencher) {
     b.iter(|| unicode_normalization::is_nfd(NFC));
 }
 
+#[cfg(feature = "alloc")]
 #[bench]
 fn bench_is_nfc_stream_safe_ascii(b: &mut Bencher) {
     b.iter(|| unicode_normalization::is_nfc_stream_safe(ASCII));
 }
 
+#[cfg(feature = "alloc")]
 #[bench]
 fn bench_is_nfc_stream_safe_normalized(b: &mut Bencher) {
     b.iter(|| unicode_normalization::is_nfc_stream_safe(NFC));
 }
 
+#[cfg(feature = "alloc")]
 #[bench]
 fn bench_is_nfc_stream_safe_not_normalized(b: &mut Bencher) {
     b.iter(|| unicode_normalization::is_nfc_stream_safe(NFD));
 }
 
+#[cfg(feature = "alloc")]
 #[bench]
 fn bench_is_nfd_stream_safe_ascii(b: &mut Bencher) {
     b.iter(|| unicode_normalization::is_nfd_stream_safe(ASCII));
 }
 
+#[cfg(feature = "alloc")]
 #[bench]
 fn bench_is_nfd_stream_safe_normalized(b: &mut Bencher) {
     b.iter(|| unicode_normalization::is_nfd_stream_safe(NFD));
 }
 
+#[cfg(feature = "alloc")]
 #[bench]
 fn bench_is_nfd_stream_safe_not_normalized(b: &mut Bencher) {
     b.iter(|| unicode_normalization::is_nfd_stream_safe(NFC));
@@ -125,3 +141,42 @@ fn bench_streamsafe_adversarial(b: &mut Bencher) {
     let s = "bo\u{0300}\u{0301}\u{0302}\u{0303}\u{0304}\u{0305}\u{0306}\u{0307}\u{0308}\u{0309}\u{030a}\u{030b}\u{030c}\u{030d}\u{030e}\u{030f}\u{0310}\u{0311}\u{0312}\u{0313}\u{0314}\u{0315}\u{0316}\u{0317}\u{0318}\u{0319}\u{031a}\u{031b}\u{031c}\u{031d}\u{032e}oom";
     b.iter(|| s.stream_safe().count());
 }
+
+#[cfg(feature = "alloc")]
+#[bench]
+fn bench_is_nfc_long(b: &mut Bencher) {
+    let long = fs::read_to_string("benches/long.txt").unwrap();
+    b.iter(|| unicode_normalization::is_nfc(&long));
+}
+
+#[cfg(feature = "alloc")]
+#[bench]
+fn bench_is_nfd_long(b: &mut Bencher) {
+    let long = fs::read_to_string("benches/long.txt").unwrap();
+    b.iter(|| unicode_normalization::is_nfd(&long));
+}
+
+#[bench]
+fn bench_nfc_mixed(b: &mut Bencher) {
+    let mixed = MIXED.repeat(100);
+    b.iter(|| mixed.nfc().count());
+}
+
+#[bench]
+fn bench_nfd_mixed(b: &mut Bencher) {
+    let mixed = MIXED.repeat(100);
+    b.iter(|| mixed.nfd().count());
+}
+
+#[bench]
+fn bench_nfkc_mixed(b: &mut Bencher) {
+    let mixed = MIXED.repeat(100);
+    b.iter(|| mixed.nfkc().count());
+}
+
+#[cfg(feature = "alloc")]
+#[bench]
+fn bench_is_nfc_mixed(b: &mut Bencher) {
+    let mixed = MIXED.repeat(100);
+    b.iter(|| unicode_normalization::is_nfc(&mixed));
+}
@@ -11,6 +11,8 @@
 # except according to those terms.
 
 # This script uses the following Unicode tables:
+# - CaseFolding.txt
+# - DerivedCoreProperties.txt
 # - DerivedNormalizationProps.txt
 # - NormalizationTest.txt
 # - UnicodeData.txt
@@ -36,8 +38,16 @@ PREAMBLE = """// Copyright 2012-2018 The Rust Project Developers. See the COPYRI
 // NOTE: The following code was generated by "scripts/unicode.py", do not edit directly
 
 #![allow(missing_docs)]
+// Some of the tables are only used with the `alloc` feature.
+#![cfg_attr(not(feature = "alloc"), allow(dead_code))]
 """
 
+# Constants from Unicode 9.0.0 Section 3.12 Conjoining Jamo Behavior
+# http://www.unicode.org/versions/Unicode9.0.0/ch03.pdf#M9.32468.Heading.310.Combining.Jamo.Behavior
+S_BASE, L_BASE, V_BASE, T_BASE = 0xAC00, 0x1100, 0x1161, 0x11A7
+L_COUNT, V_COUNT, T_COUNT = 19, 21, 28
+S_COUNT = L_COUNT * V_COUNT * T_COUNT
+
 NormalizationTest = collections.namedtuple(
     "NormalizationTest",
     ["source", "nfc", "nfd", "nfkc", "nfkd"],
@@ -62,6 +72,8 @@ class UnicodeData(object):
         self._load_unicode_data()
         self.norm_props = self._load_norm_props()
         self.norm_tests = self._load_norm_tests()
+        self.simple_folding, self.full_folding, self.turkic_folding = self._load_case_folding()
+        self.default_ignorables = self._load_default_ignorables()
 
         self.canon_comp = self._compute_canonical_comp()
         self.canon_fully_decomp, self.compat_fully_decomp = self._compute_fully_decomposed()
@@ -78,6 +90,8 @@ class UnicodeData(object):
 
         self.ss_leading, self.ss_trailing = self._compute_stream_safe_tables()
 
+        self.nfkc_casefold, self.nfkc_simple_casefold = self._compute_nfkc_casefold()
+
     def _fetch(self, filename):
         resp = urllib.request.urlopen(UCD_URL + filename)
         return resp.read().decode('utf-8')
@@ -129,6 +143,47 @@ class UnicodeData(object):
 
         return props
 
+    def _load_case_folding(self):
+        simple_folding = {}
+        full_folding = {}
+        turkic_folding = {}
+
+        for line in self._fetch("CaseFolding.txt").splitlines():
+            (folding_data, _, _) = line.partition("#")
+            folding_pieces = [p.strip() for p in folding_data.split(";")]
+
+            if len(folding_pieces) < 3:
+                continue
+
+            char_int = int(folding_pieces[0], 16)
+            status = folding_pieces[1]
+            mapping = [int(c, 16) for c in folding_pieces[2].split()]
+
+            if status in ('C', 'S'):
+                simple_folding[char_int] = mapping
+            if status in ('C', 'F'):
+                full_folding[char_int] = mapping
+            if status == 'T':
+                assert len(mapping) == 1
+                turkic_folding[char_int] = mapping[0]
+
+        return simple_folding, full_folding, turkic_folding
+
+    def _load_default_ignorables(self):
+        default_ignorables = set()
+
+        for line in self._fetch("DerivedCoreProperties.txt").splitlines():
+            (prop_data, _, _) = line.partition("#")
+            prop_pieces = [p.strip() for p in prop_data.split(";")]
+
+            if len(prop_pieces) < 2 or prop_pieces[1] != "Default_Ignorable_Code_Point":
+                continue
+
+            (low, _, high) = prop_pieces[0].partition("..")
+            default_ignorables.update(range(int(low, 16), int(high or low, 16) + 1))
+
+        return default_ignorables
+
     def _load_norm_tests(self):
         tests = []
         for line in self._fetch("NormalizationTest.txt").splitlines():
@@ -178,11 +233,6 @@ class UnicodeData(object):
         The upshot is that decomposition code is very simple and easy to inline
         at mild code size cost.
         """
-        # Constants from Unicode 9.0.0 Section 3.12 Conjoining Jamo Behavior
-        # http://www.unicode.org/versions/Unicode9.0.0/ch03.pdf#M9.32468.Heading.310.Combining.Jamo.Behavior
-        S_BASE, L_COUNT, V_COUNT, T_COUNT = 0xAC00, 19, 21, 28
-        S_COUNT = L_COUNT * V_COUNT * T_COUNT
-
         def _decompose(char_int, compatible):
             # 7-bit ASCII never decomposes
             if char_int <= 0x7f:
@@ -284,6 +334,125 @@ class UnicodeData(object):
 
         return leading_nonstarters, trailing_nonstarters
 
+    def _decompose(self, chars, compatible):
+        """
+        Normalize a list of code points to NFD, or NFKD if `compatible` is
+        set, using the tables computed above.
+        """
+        decomposed = []
+        for c in chars:
+            if S_BASE <= c < S_BASE + S_COUNT:
+                s_index = c - S_BASE
+                decomposed.append(L_BASE + s_index // (V_COUNT * T_COUNT))
+                decomposed.append(V_BASE + (s_index % (V_COUNT * T_COUNT)) // T_COUNT)
+                if s_index % T_COUNT != 0:
+                    decomposed.append(T_BASE + s_index % T_COUNT)
+            else:
+                decomposed.extend(compatible and self.compat_fully_decomp.get(c) or
+                                  self.canon_fully_decomp.get(c) or [c])
+
+        ccc = lambda c: int(self.combining_classes.get(c, 0))
+
+        # Canonical ordering: stable sort each run of non-starters.
+        i = 0
+        while i < len(decomposed):
+            j = i
+            while j < len(decomposed) and ccc(decomposed[j]) != 0:
+                j += 1
+            decomposed[i:j] = sorted(decomposed[i:j], key=ccc)
+            i = j + 1
+        return decomposed
+
+    def _nfkc(self, chars):
+        """
+        Normalize a list of code points to NFKC, using the tables computed
+        above.
+        """
+        decomposed = self._decompose(chars, True)
+        ccc = lambda c: int(self.combining_classes.get(c, 0))
+
+        def compose_pair(a, b):
+            if L_BASE <= a < L_BASE + L_COUNT and V_BASE <= b < V_BASE + V_COUNT:
+                return S_BASE + ((a - L_BASE) * V_COUNT + (b - V_BASE)) * T_COUNT
+            if (S_BASE <= a < S_BASE + S_COUNT and (a - S_BASE) % T_COUNT == 0 and
+                    T_BASE < b < T_BASE + T_COUNT):
+                return a + (b - T_BASE)
+            return self.canon_comp.get((a, b))
+
+        # Canonical composition.
+        composed = []
+        starter = None
+        last_cc = None
+        for c in decomposed:
+            cc = ccc(c)
+            if starter is not None and (last_cc is None or last_cc < cc):
+                pair = compose_pair(composed[starter], c)
+                if pair is not None:
+                    composed[starter] = pair
+                    continue
+            if cc == 0:
+                starter = len(composed)
+                last_cc = None
+            else:
+                last_cc = cc
+            composed.append(c)
+        return composed
+
+    def _compute_nfkc_casefold(self):
+        """
+        NFKC_Casefold (NFKC_CF) maps each character by repeatedly applying
+        NFKC, full case folding and removal of default ignorables until the
+        result is stable. NFKC_SimpleCasefold is derived the same way using
+        simple case folding instead. It agrees with NFKC_CF for all but a few
+        characters, so only those are stored in its table and lookups fall
+        back on the NFKC_CF table otherwise.
+
+        Since DerivedNormalizationProps.txt gives NFKC_CF directly, deriving
+        it here as well checks that the derivation is right.
+        """
+        nfkc_casefold = {}
+        for low, high, data in self.norm_props["NFKC_CF"]:
+            mapping = [int(c, 16) for c in data.split()]
+            for char_int in range(int(low, 16), int(high or low, 16) + 1):
+                nfkc_casefold[char_int] = mapping
+
+        # The mapping is only given for characters that change.
+        changes = set()
+        for low, high, _ in self.norm_props["Changes_When_NFKC_Casefolded"]:
+            changes.update(range(int(low, 16), int(high or low, 16) + 1))
+        assert set(nfkc_casefold) == changes
+
+        # Case folding is applied to the NFD of the text, so that it also
+        # reaches characters like U+0345 COMBINING GREEK YPOGEGRAMMENI inside
+        # precomposed characters.
+        def _derive(char_int, folding):
+            chars = [char_int]
+            while True:
+                folded = []
+                for c in self._decompose(chars, False):
+                    folded.extend(folding.get(c, [c]))
+                result = self._nfkc([c for c in self._nfkc(folded)
+                                     if c not in self.default_ignorables])
+                if result == chars:
+                    return result
+                chars = result
+
+        # Any other character is left alone by all three transformations.
+        candidates = (set(nfkc_casefold) | set(self.full_folding) | set(self.simple_folding) |
+                      set(self.canon_fully_decomp) | set(self.compat_fully_decomp) |
+                      self.default_ignorables)
+
+        nfkc_simple_casefold = {}
+        for char_int in sorted(candidates):
+            full = _derive(char_int, self.full_folding)
+            assert full == nfkc_casefold.get(char_int, [char_int])
+
+            simple = _derive(char_int, self.simple_folding)
+            if simple != full:
+                nfkc_simple_casefold[char_int] = simple
+
+        return nfkc_casefold, nfkc_simple_casefold
+
 hexify = lambda c: '{:04X}'.format(c)
 
 def gen_mph_data(name, d, kv_type, kv_callback):
@@ -297,95 +466,150 @@ def gen_mph_data(name, d, kv_type, kv_callback):
         out.write("    {},\n".format(kv_callback(k)))
     out.write("];\n\n")
 
-def gen_combining_class(combining_classes, out):
-    gen_mph_data('canonical_combining_class', combining_classes, 'u32',
-        lambda k: "0x{:X}".format(int(combining_classes[k]) | (k << 8)))
-
-def gen_composition_table(canon_comp, out):
-    table = {}
-    for (c1, c2), c3 in canon_comp.items():
-        if c1 < 0x10000 and c2 < 0x10000:
-            table[(c1 << 16) | c2] = c3
-    (salt, keys) = minimal_perfect_hash(table)
-    gen_mph_data('COMPOSITION_TABLE', table, '(u32, char)',
-        lambda k: "(0x%s, '\\u{%s}')" % (hexify(k), hexify(table[k])))
-
-    out.write("pub(crate) fn composition_table_astral(c1: char, c2: char) -> Option<char> {\n")
-    out.write("    match (c1, c2) {\n")
-    for (c1, c2), c3 in sorted(canon_comp.items()):
-        if c1 >= 0x10000 and c2 >= 0x10000:
-            out.write("        ('\\u{%s}', '\\u{%s}') => Some('\\u{%s}'),\n" % (hexify(c1), hexify(c2), hexify(c3)))
-
-    out.write("        _ => None,\n")
-    out.write("    }\n")
-    out.write("}\n")
+# The normalization properties of each code point are packed into a u64,
+# which src/lookups.rs unpacks:
+#
+#   bits  0..8   canonical combining class
+#   bits  8..10  NFC_QC, 0 for Yes, 1 for No and 2 for Maybe
+#   bits 10..12  NFKC_QC, likewise
+#   bit  12      NFD_QC=No
+#   bit  13      NFKD_QC=No
+#   bit  14      General_Category=Mark
+#   bits 15..17  trailing nonstarters of a decomposition
+#   bits 17..20  length of the full canonical decomposition
+#   bits 20..25  length of the full compatibility decomposition, if it
+#                differs from the canonical one
+#   bits 25..32  number of primary composites starting with the character
+#   bits 32..48  offset of the decompositions in DECOMPOSED_CHARS, the
+#                compatibility one following the canonical one
+#   bits 48..64  offset of the compositions in COMPOSITIONS
+#
+# The records are looked up in a three stage trie: the top bits of a code
+# point pick a block of the middle stage, which in turn picks a block of
+# records. Equal blocks are stored once.
+TRIE_INDEX_SHIFT, TRIE_MIDDLE_SHIFT = 10, 4
+
+def gen_trie(data, out):
+    records = collections.defaultdict(int)
+    for char, ccc in data.combining_classes.items():
+        records[char] |= int(ccc)
+
+    qc_values = {'N': 1, 'M': 2}
+    for prop, shift in [('NFC_QC', 8), ('NFKC_QC', 10), ('NFD_QC', 12), ('NFKD_QC', 13)]:
+        for low, high, value in data.norm_props[prop]:
+            assert value == 'N' or prop in ('NFC_QC', 'NFKC_QC')
+            for char in range(int(low, 16), int(high or low, 16) + 1):
+                records[char] |= qc_values[value] << shift
+
+    for char in data.general_category_mark:
+        records[char] |= 1 << 14
+
+    for char, num_trailing in data.ss_trailing.items():
+        assert num_trailing < 4
+        records[char] |= num_trailing << 15
+
+    decomposed_chars = []
+    decomposed_offsets = {}
+    for char in sorted(set(data.canon_fully_decomp) | set(data.compat_fully_decomp)):
+        canon = data.canon_fully_decomp.get(char, [])
+        compat = data.compat_fully_decomp.get(char, [])
+        assert len(canon) < 8 and len(compat) < 32
+        chars = tuple(canon + compat)
+        if chars not in decomposed_offsets:
+            decomposed_offsets[chars] = len(decomposed_chars)
+            decomposed_chars.extend(chars)
+        records[char] |= len(canon) << 17 | len(compat) << 20 | decomposed_offsets[chars] << 32
+    assert len(decomposed_chars) <= 0x10000
+
+    compositions = []
+    by_first = collections.defaultdict(list)
+    for (c1, c2), c3 in data.canon_comp.items():
+        by_first[c1].append((c2, c3))
+    for c1, pairs in sorted(by_first.items()):
+        assert len(pairs) < 0x80
+        records[c1] |= len(pairs) << 25 | len(compositions) << 48
+        compositions.extend(sorted(pairs))
+    assert len(compositions) <= 0x10000
+
+    leaf_len = 1 << TRIE_MIDDLE_SHIFT
+    block_len = 1 << TRIE_INDEX_SHIFT
+    leaves, leaf_offsets = [], {}
+    middle, middle_offsets = [], {}
+    index = []
+    for block in range(0, 0x110000, block_len):
+        leaf_indices = []
+        for start in range(block, block + block_len, leaf_len):
+            leaf = tuple(records.get(char, 0) for char in range(start, start + leaf_len))
+            if leaf not in leaf_offsets:
+                leaf_offsets[leaf] = len(leaves)
+                leaves.extend(leaf)
+            leaf_indices.append(leaf_offsets[leaf])
+        leaf_indices = tuple(leaf_indices)
+        if leaf_indices not in middle_offsets:
+            middle_offsets[leaf_indices] = len(middle)
+            middle.extend(leaf_indices)
+        index.append(middle_offsets[leaf_indices])
+    assert len(leaves) <= 0x10000 and len(middle) <= 0x10000
+
+    # The tables are statics rather than consts, so that inlining the lookup
+    # doesn't make several copies of them.
+    out.write("pub(crate) const TRIE_INDEX_SHIFT: u32 = %d;\n" % TRIE_INDEX_SHIFT)
+    out.write("pub(crate) const TRIE_MIDDLE_SHIFT: u32 = %d;\n" % TRIE_MIDDLE_SHIFT)
+    tables = [('TRIE_INDEX', 'u16', index, "0x{:x}"),
+              ('TRIE_MIDDLE', 'u16', middle, "0x{:x}"),
+              ('TRIE_LEAVES', 'u64', leaves, "0x{:x}")]
+    for name, ty, values, fmt in tables:
+        out.write("pub(crate) static %s: &[%s] = &[\n" % (name, ty))
+        for value in values:
+            out.write("    %s,\n" % fmt.format(value))
+        out.write("];\n")
+    out.write("\n")
 
-def gen_decomposition_tables(canon_decomp, compat_decomp, out):
-    tables = [(canon_decomp, 'canonical'), (compat_decomp, 'compatibility')]
-    for table, name in tables:
-        gen_mph_data(name + '_decomposed', table, "(u32, &'static [char])",
-            lambda k: "(0x{:x}, &[{}])".format(k,
-                ", ".join("'\\u{%s}'" % hexify(c) for c in table[k])))
+    out.write("pub(crate) static DECOMPOSED_CHARS: &[char] = &[\n")
+    for char in decomposed_chars:
+        out.write("    '\\u{%s}',\n" % hexify(char))
+    out.write("];\n")
+    out.write("pub(crate) static COMPOSITIONS: &[(char, char)] = &[\n")
+    for c2, c3 in compositions:
+        out.write("    ('\\u{%s}', '\\u{%s}'),\n" % (hexify(c2), hexify(c3)))
+    out.write("];\n")
 
-def gen_qc_match(prop_table, out):
+def gen_stream_safe(leading, out):
+    # This could be done as a hash but the table is very small.
+    out.write("#[inline]\n")
+    out.write("pub fn stream_safe_leading_nonstarters(c: char) -> usize {\n")
     out.write("    match c {\n")
 
-    for low, high, data in prop_table:
-        assert data in ('N', 'M')
-        result = "No" if data == 'N' else "Maybe"
-        if high:
-            out.write(r"        '\u{%s}'...'\u{%s}' => %s," % (low, high, result))
-        else:
-            out.write(r"        '\u{%s}' => %s," % (low, result))
-        out.write("\n")
+    for char, num_leading in sorted(leading.items()):
+        out.write("        '\\u{%s}' => %d,\n" % (hexify(char), num_leading))
 
-    out.write("        _ => Yes,\n")
+    out.write("        _ => 0,\n")
     out.write("    }\n")
-
-def gen_nfc_qc(prop_tables, out):
-    out.write("#[inline]\n")
-    out.write("pub fn qc_nfc(c: char) -> IsNormalized {\n")
-    gen_qc_match(prop_tables['NFC_QC'], out)
-    out.write("}\n")
-
-def gen_nfkc_qc(prop_tables, out):
-    out.write("#[inline]\n")
-    out.write("pub fn qc_nfkc(c: char) -> IsNormalized {\n")
-    gen_qc_match(prop_tables['NFKC_QC'], out)
     out.write("}\n")
 
-def gen_nfd_qc(prop_tables, out):
-    out.write("#[inline]\n")
-    out.write("pub fn qc_nfd(c: char) -> IsNormalized {\n")
-    gen_qc_match(prop_tables['NFD_QC'], out)
-    out.write("}\n")
-
-def gen_nfkd_qc(prop_tables, out):
-    out.write("#[inline]\n")
-    out.write("pub fn qc_nfkd(c: char) -> IsNormalized {\n")
-    gen_qc_match(prop_tables['NFKD_QC'], out)
-    out.write("}\n")
+def gen_nfkc_casefold_tables(nfkc_casefold, nfkc_simple_casefold, out):
+    tables = [(nfkc_casefold, 'nfkc_casefold'), (nfkc_simple_casefold, 'nfkc_simple_casefold')]
+    for table, name in tables:
+        gen_mph_data(name, table, "(u32, &[char])",
+            lambda k: "(0x{:x}, &[{}])".format(k,
+                ", ".join("'\\u{%s}'" % hexify(c) for c in table[k])))
 
-def gen_combining_mark(general_category_mark, out):
-    gen_mph_data('combining_mark', general_category_mark, 'u32',
-        lambda k: '0x{:04x}'.format(k))
+def gen_case_folding_tables(full_folding, turkic_folding, out):
+    gen_mph_data('case_folding', full_folding, "(u32, &[char])",
+        lambda k: "(0x{:x}, &[{}])".format(k,
+            ", ".join("'\\u{%s}'" % hexify(c) for c in full_folding[k])))
 
-def gen_stream_safe(leading, trailing, out):
-    # This could be done as a hash but the table is very small.
+    # The Turkic mappings override only a couple of characters.
     out.write("#[inline]\n")
-    out.write("pub fn stream_safe_leading_nonstarters(c: char) -> usize {\n")
+    out.write("pub fn turkic_case_folding(c: char) -> Option<char> {\n")
     out.write("    match c {\n")
 
-    for char, num_leading in sorted(leading.items()):
-        out.write("        '\\u{%s}' => %d,\n" % (hexify(char), num_leading))
+    for char, mapping in sorted(turkic_folding.items()):
+        out.write("        '\\u{%s}' => Some('\\u{%s}'),\n" % (hexify(char), hexify(mapping)))
 
-    out.write("        _ => 0,\n")
+    out.write("        _ => None,\n")
     out.write("    }\n")
     out.write("}\n")
-    out.write("\n")
-
-    gen_mph_data('trailing_nonstarters', trailing, 'u32',
-        lambda k: "0x{:X}".format(int(trailing[k]) | (k << 8)))
 
 def gen_tests(tests, out):
     out.write("""#[derive(Debug)]
@@ -473,39 +697,20 @@ if __name__ == '__main__':
     data = UnicodeData()
     with open("tables.rs", "w", newline = "\n") as out:
         out.write(PREAMBLE)
-        out.write("use quick_check::IsNormalized;\n")
-        out.write("use quick_check::IsNormalized::*;\n")
-        out.write("\n")
 
         version = "(%s, %s, %s)" % tuple(UNICODE_VERSION.split("."))
         out.write("#[allow(unused)]\n")
         out.write("pub const UNICODE_VERSION: (u64, u64, u64) = %s;\n\n" % version)
 
-        gen_combining_class(data.combining_classes, out)
+        gen_trie(data, out)
         out.write("\n")
 
-        gen_composition_table(data.canon_comp, out)
+        gen_stream_safe(data.ss_leading, out)
         out.write("\n")
 
-        gen_decomposition_tables(data.canon_fully_decomp, data.compat_fully_decomp, out)
-
-        gen_combining_mark(data.general_category_mark, out)
-        out.write("\n")
+        gen_nfkc_casefold_tables(data.nfkc_casefold, data.nfkc_simple_casefold, out)
 
-        gen_nfc_qc(data.norm_props, out)
-        out.write("\n")
-
-        gen_nfkc_qc(data.norm_props, out)
-        out.write("\n")
-
-        gen_nfd_qc(data.norm_props, out)
-        out.write("\n")
-
-        gen_nfkd_qc(data.norm_props, out)
-        out.write("\n")
-
-        gen_stream_safe(data.ss_leading, data.ss_trailing, out)
-        out.write("\n")
+        gen_case_folding_tables(data.full_folding, data.turkic_folding, out)
 
     with open("normalization_tests.rs", "w", newline = "\n") as out:
         out.write(PREAMBLE)
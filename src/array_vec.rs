@@ -0,0 +1,98 @@
+// Copyright 2012-2015 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A fixed-capacity stand-in for `SmallVec`, used by the normalizing
+//! iterators when the `alloc` feature is disabled.
+
+use core::ops::{Deref, DerefMut};
+
+/// The arrays an `ArrayVec` can be backed by.
+pub(crate) trait Array {
+    type Item: Copy;
+    fn new() -> Self;
+    fn as_slice(&self) -> &[Self::Item];
+    fn as_mut_slice(&mut self) -> &mut [Self::Item];
+}
+
+macro_rules! impl_array {
+    ($($n:expr),*) => {
+        $(
+            impl<T: Copy + Default> Array for [T; $n] {
+                type Item = T;
+
+                #[inline]
+                fn new() -> Self {
+                    [T::default(); $n]
+                }
+
+                #[inline]
+                fn as_slice(&self) -> &[T] {
+                    self
+                }
+
+                #[inline]
+                fn as_mut_slice(&mut self) -> &mut [T] {
+                    self
+                }
+            }
+        )*
+    }
+}
+
+impl_array!(32, 64);
+
+#[derive(Clone)]
+pub(crate) struct ArrayVec<A> {
+    data: A,
+    len: usize,
+}
+
+impl<A: Array> ArrayVec<A> {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        ArrayVec { data: A::new(), len: 0 }
+    }
+
+    /// Panics if the array is full: callers are expected to keep what they
+    /// buffer bounded.
+    #[inline]
+    pub(crate) fn push(&mut self, item: A::Item) {
+        self.data.as_mut_slice()[self.len] = item;
+        self.len += 1;
+    }
+
+    #[inline]
+    pub(crate) fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            self.len = len;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl<A: Array> Deref for ArrayVec<A> {
+    type Target = [A::Item];
+
+    #[inline]
+    fn deref(&self) -> &[A::Item] {
+        &self.data.as_slice()[..self.len]
+    }
+}
+
+impl<A: Array> DerefMut for ArrayVec<A> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [A::Item] {
+        &mut self.data.as_mut_slice()[..self.len]
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Skipping over runs of text below U+0300 several bytes at a time.
+//!
+//! Every character below U+0300 is a starter with the quick check property
+//! `Yes` for NFC. For NFD that's only true below U+00C0, where the Latin-1
+//! letters with diacritics start, and for NFKC and NFKD the Latin-1
+//! characters with compatibility decompositions are left out as well.
+//!
+//! Only code working on a whole `str` can skip such runs in bulk: the quick
+//! checks and the string functions do, through `PlainRuns`. The normalizing
+//! iterators are handed one character at a time, so they just let these
+//! characters through without any table lookups, through `is_plain`.
+
+use form::Form;
+
+/// The Latin-1 characters from U+0080 to U+00BF with a compatibility
+/// decomposition, as bits indexed by their offset from U+0080: U+00A0,
+/// U+00A8, U+00AA, U+00AF, U+00B2 to U+00B5, U+00B8 to U+00BA and U+00BC to
+/// U+00BE.
+const LATIN1_COMPATIBILITY: u64 = 0x773c_8501_0000_0000;
+
+const LO: u64 = 0x0101_0101_0101_0101;
+const HI: u64 = 0x8080_8080_8080_8080;
+
+/// The first UTF-8 byte of the text that can't be skipped in bulk for
+/// `form`. For the compatibility forms everything past ASCII is checked one
+/// character at a time.
+#[inline]
+fn byte_limit(form: Form) -> u8 {
+    match form {
+        // U+0300
+        Form::Nfc => 0xcc,
+        // U+00C0
+        Form::Nfd => 0xc3,
+        Form::Nfkc | Form::Nfkd => 0x80,
+    }
+}
+
+/// Whether `c` is below U+0300, a starter, and has the quick check property
+/// `Yes` for `form`.
+#[inline]
+pub(crate) fn is_plain(c: char, form: Form) -> bool {
+    match form {
+        Form::Nfc => c < '\u{300}',
+        Form::Nfd => c < '\u{c0}',
+        Form::Nfkc | Form::Nfkd => {
+            c < '\u{80}' || (c < '\u{c0}' && LATIN1_COMPATIBILITY & (1 << (c as u32 - 0x80)) == 0)
+        }
+    }
+}
+
+/// Returns the length of the longest prefix of `bytes` with no byte of
+/// `limit` or more, which is at least 0x80. This looks at eight bytes at a
+/// time.
+fn bytes_below(bytes: &[u8], limit: u8) -> usize {
+    // A byte is at least `limit` if its high bit is set and adding this to
+    // its low bits carries into it. The sum never carries into the next byte.
+    let add = LO * (0x100 - u64::from(limit));
+    let mut i = 0;
+    for chunk in bytes.chunks_exact(8) {
+        let mut word = [0; 8];
+        word.copy_from_slice(chunk);
+        let word = u64::from_le_bytes(word);
+        let at_least = word & ((word & !HI) + add) & HI;
+        if at_least != 0 {
+            return i + (at_least.trailing_zeros() / 8) as usize;
+        }
+        i += 8;
+    }
+    i + bytes[i..].iter().take_while(|&&b| b < limit).count()
+}
+
+/// Returns the length of the longest prefix of `s` made of characters for
+/// which `is_plain` holds.
+#[inline]
+pub(crate) fn plain_prefix_len(s: &str, form: Form) -> usize {
+    let bytes = s.as_bytes();
+    let limit = byte_limit(form);
+    let mut i = 0;
+    loop {
+        // The scan stops at a byte that starts a character, since all
+        // continuation bytes are below any limit.
+        i += bytes_below(&bytes[i..], limit);
+        // Step over the Latin-1 characters without a compatibility
+        // decomposition one at a time.
+        match bytes.get(i..i + 2) {
+            Some(&[0xc2, b]) if limit == 0x80 && LATIN1_COMPATIBILITY & (1 << (b & 0x3f)) == 0 => {
+                i += 2;
+            }
+            _ => return i,
+        }
+    }
+}
+
+/// Like `str::char_indices`, but replacing each run of at least two
+/// characters for which `is_plain` holds with its last character.
+///
+/// The characters left out don't change the outcome of the quick check or
+/// where it finds the last starter.
+#[derive(Clone)]
+pub(crate) struct PlainRuns<'a> {
+    s: &'a str,
+    pos: usize,
+    form: Form,
+}
+
+impl<'a> PlainRuns<'a> {
+    #[inline]
+    pub(crate) fn new(s: &'a str, form: Form) -> PlainRuns<'a> {
+        PlainRuns { s, pos: 0, form }
+    }
+}
+
+impl<'a> Iterator for PlainRuns<'a> {
+    type Item = (usize, char);
+
+    #[inline]
+    fn next(&mut self) -> Option<(usize, char)> {
+        let rest = &self.s[self.pos..];
+        let first = *rest.as_bytes().first()?;
+        let mut start = self.pos;
+        // Only characters below U+0300 can start a run.
+        if first < 0xcc {
+            let n = plain_prefix_len(rest, self.form);
+            if n > 1 {
+                // Back up to the start of the last character of the run.
+                start += n - 1;
+                while !self.s.is_char_boundary(start) {
+                    start -= 1;
+                }
+            }
+        }
+        let c = self.s[start..].chars().next()?;
+        self.pos = start + c.len_utf8();
+        Some((start, c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PlainRuns, bytes_below, is_plain, plain_prefix_len};
+    use std::char;
+    use std::string::String;
+    use std::vec::Vec;
+    use form::Form;
+    use lookups::canonical_combining_class;
+    use quick_check::IsNormalized;
+
+    #[test]
+    fn test_is_plain() {
+        for &form in &[Form::Nfc, Form::Nfd, Form::Nfkc, Form::Nfkd] {
+            for c in (0..0x300).filter_map(char::from_u32) {
+                if is_plain(c, form) {
+                    assert_eq!(canonical_combining_class(c), 0);
+                    assert_eq!(form.qc()(c), IsNormalized::Yes, "{:?} {:?}", form, c);
+                }
+            }
+            for c in (0..0xc0).filter_map(char::from_u32) {
+                assert_eq!(is_plain(c, form), form.qc()(c) == IsNormalized::Yes);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bytes_below() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        for &limit in &[0x80, 0xc3, 0xcc, 0xff] {
+            for start in 0..bytes.len() {
+                let expected = bytes[start..].iter().take_while(|&&b| b < limit).count();
+                assert_eq!(bytes_below(&bytes[start..], limit), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_plain_runs() {
+        let s = "abc\u{e9}\u{a0}\u{b0}d\u{301}x\u{2fc}\u{300}\u{e00}yz";
+        for &form in &[Form::Nfc, Form::Nfd, Form::Nfkc, Form::Nfkd] {
+            let all: Vec<(usize, char)> = s.char_indices().collect();
+            let runs: Vec<(usize, char)> = PlainRuns::new(s, form).collect();
+            // The characters kept are those that don't belong to a run, and
+            // the last of each run.
+            let mut expected = Vec::new();
+            for (i, &(offset, c)) in all.iter().enumerate() {
+                let next_plain = all.get(i + 1).map_or(false, |&(_, d)| is_plain(d, form));
+                if !is_plain(c, form) || !next_plain {
+                    expected.push((offset, c));
+                }
+            }
+            assert_eq!(runs, expected, "{:?}", form);
+
+            let n = plain_prefix_len(s, form);
+            let plain: String = s.chars().take_while(|&c| is_plain(c, form)).collect();
+            assert_eq!(n, plain.len());
+        }
+        assert_eq!(plain_prefix_len("", Form::Nfc), 0);
+        assert_eq!(PlainRuns::new("", Form::Nfc).next(), None);
+    }
+}
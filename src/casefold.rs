@@ -0,0 +1,70 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use decompose::{self, Decompositions};
+use lookups::{nfkc_casefold, nfkc_simple_casefold};
+
+/// External iterator replacing each character of a string's canonical
+/// decomposition by its NFKC_Casefold or NFKC_SimpleCasefold mapping.
+///
+/// Mapping the canonical decomposition rather than the string itself makes
+/// sure canonically equivalent strings are mapped the same way, since some
+/// combining marks map to starters. The result still has to be put in NFC,
+/// which `nfkc_casefold` and `nfkc_simple_casefold` take care of.
+#[derive(Clone)]
+pub struct Casefold<I> {
+    iter: Decompositions<I>,
+    simple: bool,
+    // The rest of the mapping of the last character.
+    mapped: &'static [char],
+}
+
+#[inline]
+pub fn new_full<I: Iterator<Item=char>>(iter: I) -> Casefold<I> {
+    Casefold { iter: decompose::new_canonical(iter), simple: false, mapped: &[] }
+}
+
+#[inline]
+pub fn new_simple<I: Iterator<Item=char>>(iter: I) -> Casefold<I> {
+    Casefold { iter: decompose::new_canonical(iter), simple: true, mapped: &[] }
+}
+
+impl<I: Iterator<Item=char>> Iterator for Casefold<I> {
+    type Item = char;
+
+    #[inline]
+    fn next(&mut self) -> Option<char> {
+        loop {
+            if let Some((&ch, rest)) = self.mapped.split_first() {
+                self.mapped = rest;
+                return Some(ch);
+            }
+            let ch = self.iter.next()?;
+            // ASCII only ever changes by lowercasing.
+            if ch <= '\x7f' {
+                return Some(ch.to_ascii_lowercase());
+            }
+            let mapping = if self.simple {
+                nfkc_simple_casefold(ch)
+            } else {
+                nfkc_casefold(ch)
+            };
+            match mapping {
+                Some(mapping) => self.mapped = mapping,
+                None => return Some(ch),
+            }
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.mapped.len(), None)
+    }
+}
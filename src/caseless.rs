@@ -0,0 +1,205 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Caseless matching as defined in section 3.13 of the Unicode Standard.
+
+use smallvec::SmallVec;
+use UnicodeNormalization;
+use decompose::{self, Decompositions};
+use lookups::{canonical_combining_class, case_folding};
+use tables::turkic_case_folding;
+
+/// The case folding used for caseless matching.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum CaseFolding {
+    /// Full case folding with the default mappings, under which U+0049 LATIN
+    /// CAPITAL LETTER I folds to U+0069 LATIN SMALL LETTER I.
+    Default,
+    /// Full case folding with the Turkic mappings, under which U+0049 LATIN
+    /// CAPITAL LETTER I folds to U+0131 LATIN SMALL LETTER DOTLESS I, and
+    /// U+0049 followed by U+0307 COMBINING DOT ABOVE folds to U+0069 LATIN
+    /// SMALL LETTER I.
+    Turkic,
+}
+
+/// External iterator replacing each character by its full case folding.
+#[derive(Clone)]
+struct Fold<I> {
+    iter: I,
+    turkic: bool,
+    // The rest of the mapping of the last character.
+    mapped: &'static [char],
+    // Characters read ahead after a capital I, in reverse order.
+    pushback: SmallVec<[char; 4]>,
+}
+
+impl<I: Iterator<Item=char>> Fold<I> {
+    #[inline]
+    fn new(iter: I, folding: CaseFolding) -> Fold<I> {
+        Fold {
+            iter,
+            turkic: folding == CaseFolding::Turkic,
+            mapped: &[],
+            pushback: SmallVec::new(),
+        }
+    }
+
+    #[inline]
+    fn next_input(&mut self) -> Option<char> {
+        self.pushback.pop().or_else(|| self.iter.next())
+    }
+
+    /// Consume a U+0307 COMBINING DOT ABOVE following a capital I, if any.
+    ///
+    /// The Turkic mappings alone don't preserve canonical equivalence, since
+    /// U+0130 LATIN CAPITAL LETTER I WITH DOT ABOVE decomposes to a capital I
+    /// followed by a dot. Like the `After_I` condition of SpecialCasing.txt,
+    /// the dot belongs to the I unless a starter or another character above
+    /// comes first.
+    fn take_dot_above(&mut self) -> bool {
+        let mut seen = SmallVec::<[char; 4]>::new();
+        let mut found = false;
+        while let Some(ch) = self.next_input() {
+            if ch == '\u{307}' {
+                found = true;
+                break;
+            }
+            seen.push(ch);
+            let class = canonical_combining_class(ch);
+            if class == 0 || class == 230 {
+                break;
+            }
+        }
+        self.pushback.extend(seen.into_iter().rev());
+        found
+    }
+}
+
+impl<I: Iterator<Item=char>> Iterator for Fold<I> {
+    type Item = char;
+
+    #[inline]
+    fn next(&mut self) -> Option<char> {
+        if let Some((&ch, rest)) = self.mapped.split_first() {
+            self.mapped = rest;
+            return Some(ch);
+        }
+        let ch = self.next_input()?;
+        if self.turkic {
+            if ch == 'I' {
+                return Some(if self.take_dot_above() { 'i' } else { '\u{131}' });
+            }
+            if let Some(folded) = turkic_case_folding(ch) {
+                return Some(folded);
+            }
+        }
+        if ch <= '\x7f' {
+            return Some(ch.to_ascii_lowercase());
+        }
+        match case_folding(ch) {
+            Some(mapping) => {
+                self.mapped = &mapping[1..];
+                Some(mapping[0])
+            }
+            None => Some(ch),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.mapped.len() + self.pushback.len(), None)
+    }
+}
+
+/// External iterator for the key compared by canonical caseless matching,
+/// `NFD(toCasefold(NFD(X)))`.
+#[derive(Clone)]
+pub struct CanonicalCaseless<I> {
+    iter: Decompositions<Fold<Decompositions<I>>>,
+}
+
+#[inline]
+pub fn new_canonical<I: Iterator<Item=char>>(iter: I, folding: CaseFolding) -> CanonicalCaseless<I> {
+    let folded = Fold::new(decompose::new_canonical(iter), folding);
+    CanonicalCaseless { iter: decompose::new_canonical(folded) }
+}
+
+impl<I: Iterator<Item=char>> Iterator for CanonicalCaseless<I> {
+    type Item = char;
+
+    #[inline]
+    fn next(&mut self) -> Option<char> {
+        self.iter.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// External iterator for the key compared by compatibility caseless matching,
+/// `NFKD(toCasefold(NFKD(toCasefold(NFD(X)))))`.
+#[derive(Clone)]
+pub struct CompatibilityCaseless<I> {
+    iter: Decompositions<Fold<Decompositions<Fold<Decompositions<I>>>>>,
+}
+
+#[inline]
+pub fn new_compatible<I: Iterator<Item=char>>(iter: I, folding: CaseFolding) -> CompatibilityCaseless<I> {
+    let folded = Fold::new(decompose::new_canonical(iter), folding);
+    let folded = Fold::new(decompose::new_compatible(folded), folding);
+    CompatibilityCaseless { iter: decompose::new_compatible(folded) }
+}
+
+impl<I: Iterator<Item=char>> Iterator for CompatibilityCaseless<I> {
+    type Item = char;
+
+    #[inline]
+    fn next(&mut self) -> Option<char> {
+        self.iter.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Check whether two strings are canonical caseless matches (D145), that is
+/// whether they're equal once case differences are folded away with
+/// `folding` and any canonical equivalence is taken into account.
+///
+/// ```rust
+/// use unicode_normalization::{CaseFolding, canonical_caseless_eq};
+///
+/// assert!(canonical_caseless_eq("Stra\u{df}e", "STRASSE", CaseFolding::Default));
+/// assert!(canonical_caseless_eq("\u{212b}", "a\u{30a}", CaseFolding::Default));
+/// assert!(!canonical_caseless_eq("\u{2460}", "1", CaseFolding::Default));
+/// assert!(canonical_caseless_eq("I", "\u{131}", CaseFolding::Turkic));
+/// ```
+pub fn canonical_caseless_eq(a: &str, b: &str, folding: CaseFolding) -> bool {
+    a == b || a.canonical_caseless(folding).eq(b.canonical_caseless(folding))
+}
+
+/// Check whether two strings are compatibility caseless matches (D146), that
+/// is whether they're equal once case differences are folded away with
+/// `folding` and any compatibility equivalence is taken into account.
+///
+/// ```rust
+/// use unicode_normalization::{CaseFolding, compatibility_caseless_eq};
+///
+/// assert!(compatibility_caseless_eq("\u{2460}", "1", CaseFolding::Default));
+/// assert!(compatibility_caseless_eq("\u{1c5}", "DZ\u{30c}", CaseFolding::Default));
+/// assert!(compatibility_caseless_eq("\u{130}", "i", CaseFolding::Turkic));
+/// ```
+pub fn compatibility_caseless_eq(a: &str, b: &str, folding: CaseFolding) -> bool {
+    a == b || a.compatibility_caseless(folding).eq(b.compatibility_caseless(folding))
+}
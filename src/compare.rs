@@ -0,0 +1,155 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Comparison of strings under canonical or compatibility equivalence.
+
+use core::cmp::Ordering;
+use decompose;
+use form::Form;
+
+/// The byte offset up to which `a` and `b` are identical and split at a
+/// normalization boundary for `form`, so only the text after it needs
+/// comparing.
+fn common_prefix_len(a: &str, b: &str, form: Form) -> usize {
+    let mut n = a.bytes().zip(b.bytes()).take_while(|&(x, y)| x == y).count();
+    // Identical bytes up to a char boundary in one string make up whole chars
+    // in the other one too.
+    while !a.is_char_boundary(n) {
+        n -= 1;
+    }
+    let boundary_at = |s: &str| s[n..].chars().next().map_or(true, |c| form.has_boundary_before(c));
+    if boundary_at(a) && boundary_at(b) {
+        return n;
+    }
+    a[..n].char_indices()
+        .rev()
+        .find(|&(_, c)| form.has_boundary_before(c))
+        .map_or(0, |(i, _)| i)
+}
+
+/// Check whether two strings are canonically equivalent, that is whether
+/// they have the same NFD.
+///
+/// The decompositions of both strings are compared as they're produced, so
+/// this returns as soon as they differ without building either of them.
+/// Identical leading bytes are skipped over without decomposing them.
+///
+/// ```rust
+/// use unicode_normalization::canonically_eq;
+///
+/// assert!(canonically_eq("Introducci\u{f3}n", "Introduccio\u{301}n"));
+/// assert!(!canonically_eq("Introducci\u{f3}n", "Introduccion"));
+/// ```
+pub fn canonically_eq(a: &str, b: &str) -> bool {
+    if a == b {
+        return true;
+    }
+    let n = common_prefix_len(a, b, Form::Nfd);
+    decompose::new_canonical(a[n..].chars()).eq(decompose::new_canonical(b[n..].chars()))
+}
+
+/// Check whether two strings are compatibility equivalent, that is whether
+/// they have the same NFKD.
+///
+/// Like `canonically_eq`, this compares the decompositions as they're
+/// produced and skips over identical leading bytes.
+///
+/// ```rust
+/// use unicode_normalization::compatibly_eq;
+///
+/// assert!(compatibly_eq("\u{fb01}le", "file"));
+/// assert!(!compatibly_eq("\u{fb01}le", "File"));
+/// ```
+pub fn compatibly_eq(a: &str, b: &str) -> bool {
+    if a == b {
+        return true;
+    }
+    let n = common_prefix_len(a, b, Form::Nfkd);
+    decompose::new_compatible(a[n..].chars()).eq(decompose::new_compatible(b[n..].chars()))
+}
+
+/// Compare two strings by the code points of their NFD, so canonically
+/// equivalent strings compare equal.
+///
+/// Like `canonically_eq`, this compares the decompositions as they're
+/// produced and skips over identical leading bytes.
+///
+/// ```rust
+/// use std::cmp::Ordering;
+/// use unicode_normalization::canonical_cmp;
+///
+/// assert_eq!(canonical_cmp("Introducci\u{f3}n", "Introduccio\u{301}n"), Ordering::Equal);
+/// assert_eq!(canonical_cmp("\u{f3}", "p"), Ordering::Less);
+/// ```
+pub fn canonical_cmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+    let n = common_prefix_len(a, b, Form::Nfd);
+    decompose::new_canonical(a[n..].chars()).cmp(decompose::new_canonical(b[n..].chars()))
+}
+
+/// A wrapper comparing strings by `canonical_cmp`, for use as keys in sorted
+/// containers like `BTreeMap`.
+///
+/// Canonically equivalent strings are equal under this ordering, so they end
+/// up as the same key.
+///
+/// ```rust
+/// use std::collections::BTreeMap;
+/// use unicode_normalization::CanonicalOrd;
+///
+/// let mut names = BTreeMap::new();
+/// names.insert(CanonicalOrd("Ame\u{301}lie"), 1);
+/// names.insert(CanonicalOrd("Am\u{e9}lie"), 2);
+/// assert_eq!(names.len(), 1);
+/// assert_eq!(names[&CanonicalOrd("Am\u{e9}lie")], 2);
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CanonicalOrd<S>(pub S);
+
+impl<S: AsRef<str>> PartialEq for CanonicalOrd<S> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        canonically_eq(self.0.as_ref(), other.0.as_ref())
+    }
+}
+
+impl<S: AsRef<str>> Eq for CanonicalOrd<S> {}
+
+impl<S: AsRef<str>> PartialOrd for CanonicalOrd<S> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<S: AsRef<str>> Ord for CanonicalOrd<S> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        canonical_cmp(self.0.as_ref(), other.0.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::common_prefix_len;
+    use form::Form;
+
+    #[test]
+    fn test_common_prefix_len() {
+        assert_eq!(common_prefix_len("abc", "abd", Form::Nfd), 2);
+        assert_eq!(common_prefix_len("abc", "ab", Form::Nfd), 2);
+        assert_eq!(common_prefix_len("\u{e9}", "\u{e8}", Form::Nfd), 0);
+        assert_eq!(common_prefix_len("xa\u{323}\u{301}", "xa\u{301}\u{323}", Form::Nfd), 1);
+        assert_eq!(common_prefix_len("ab\u{327}", "abc", Form::Nfd), 1);
+        assert_eq!(common_prefix_len("a\u{fb01}", "a\u{fb02}", Form::Nfkd), 1);
+    }
+}
@@ -7,10 +7,29 @@
 // <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
+#[cfg(feature = "alloc")]
 use smallvec::SmallVec;
-use std::fmt::{self, Write};
-use std::iter::Fuse;
-use std::ops::Range;
+#[cfg(not(feature = "alloc"))]
+use array_vec::ArrayVec;
+use core::fmt::{self, Write};
+use bulk::is_plain;
+use form::Form;
+use normalize::decompose_with_class;
+use core::iter::Fuse;
+use core::ops::Range;
+
+/// Without an allocator, the longest run of nonstarters the iterators can put
+/// in canonical order. Stream-safe text has at most 30.
+#[cfg(not(feature = "alloc"))]
+pub(crate) const MAX_BUFFERED_NONSTARTERS: usize = 32;
+
+#[cfg(feature = "alloc")]
+type Buffer = SmallVec<[(u8, char); 4]>;
+// Without an allocator the pending block is kept to at most one starter and
+// `MAX_BUFFERED_NONSTARTERS` nonstarters, to which a single character can add
+// up to 18 characters of its decomposition.
+#[cfg(not(feature = "alloc"))]
+type Buffer = ArrayVec<[(u8, char); 64]>;
 
 #[derive(Clone)]
 enum DecompositionType {
@@ -32,7 +51,7 @@ pub struct Decompositions<I> {
     // 2) "Ready" characters which are sorted and ready to emit on demand;
     // 3) A "pending" block which stills needs more characters for us to be able
     //    to sort in canonical order and is not safe to emit.
-    buffer: SmallVec<[(u8, char); 4]>,
+    buffer: Buffer,
     ready: Range<usize>,
 }
 
@@ -41,7 +60,7 @@ pub fn new_canonical<I: Iterator<Item=char>>(iter: I) -> Decompositions<I> {
     Decompositions {
         kind: self::DecompositionType::Canonical,
         iter: iter.fuse(),
-        buffer: SmallVec::new(),
+        buffer: Buffer::new(),
         ready: 0..0,
     }
 }
@@ -51,23 +70,42 @@ pub fn new_compatible<I: Iterator<Item=char>>(iter: I) -> Decompositions<I> {
     Decompositions {
         kind: self::DecompositionType::Compatible,
         iter: iter.fuse(),
-        buffer: SmallVec::new(),
+        buffer: Buffer::new(),
         ready: 0..0,
     }
 }
 
 impl<I> Decompositions<I> {
     #[inline]
-    fn push_back(&mut self, ch: char) {
-        let class = super::char::canonical_combining_class(ch);
-
+    fn push_back(&mut self, class: u8, ch: char) {
         if class == 0 {
             self.sort_pending();
+        } else {
+            #[cfg(not(feature = "alloc"))]
+            self.check_nonstarters();
         }
 
         self.buffer.push((class, ch));
     }
 
+    // Without an allocator there's no room to reorder a run of more than
+    // `MAX_BUFFERED_NONSTARTERS` nonstarters, and changing the text instead
+    // would give different results depending on the features enabled.
+    #[cfg(not(feature = "alloc"))]
+    #[inline]
+    fn check_nonstarters(&self) {
+        let pending = &self.buffer[self.ready.end..];
+        let starters = match pending.first() {
+            Some(&(0, _)) => 1,
+            _ => 0,
+        };
+        if pending.len() - starters >= MAX_BUFFERED_NONSTARTERS {
+            panic!("more than {} nonstarters in a row need the `alloc` feature",
+                   MAX_BUFFERED_NONSTARTERS);
+        }
+    }
+
+    #[cfg(feature = "alloc")]
     #[inline]
     fn sort_pending(&mut self) {
         // NB: `sort_by_key` is stable, so it will preserve the original text's
@@ -76,6 +114,22 @@ impl<I> Decompositions<I> {
         self.ready.end = self.buffer.len();
     }
 
+    // `core` has no stable sort, but the pending block is short, so an
+    // insertion sort does.
+    #[cfg(not(feature = "alloc"))]
+    #[inline]
+    fn sort_pending(&mut self) {
+        let pending = &mut self.buffer[self.ready.end..];
+        for i in 1..pending.len() {
+            let mut j = i;
+            while j > 0 && pending[j - 1].0 > pending[j].0 {
+                pending.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+        self.ready.end = self.buffer.len();
+    }
+
     #[inline]
     fn reset_buffer(&mut self) {
         // Equivalent to `self.buffer.drain(0..self.ready.end)` (if SmallVec
@@ -105,14 +159,31 @@ impl<I: Iterator<Item=char>> Iterator for Decompositions<I> {
     #[inline]
     fn next(&mut self) -> Option<char> {
         while self.ready.end == 0 {
-            match (self.iter.next(), &self.kind) {
-                (Some(ch), &DecompositionType::Canonical) => {
-                    super::char::decompose_canonical(ch, |d| self.push_back(d));
+            let ch = self.iter.next();
+            let form = match self.kind {
+                DecompositionType::Canonical => Form::Nfd,
+                DecompositionType::Compatible => Form::Nfkd,
+            };
+            if let Some(ch) = ch.filter(|&ch| is_plain(ch, form)) {
+                // A starter that doesn't decompose can go out as soon as
+                // everything before it has, without looking it up.
+                if self.buffer.is_empty() {
+                    return Some(ch);
                 }
-                (Some(ch), &DecompositionType::Compatible) => {
-                    super::char::decompose_compatible(ch, |d| self.push_back(d));
+                self.sort_pending();
+                self.buffer.push((0, ch));
+                self.ready.end = self.buffer.len();
+                break;
+            }
+            match ch {
+                Some(ch) => {
+                    let compatible = match self.kind {
+                        DecompositionType::Canonical => false,
+                        DecompositionType::Compatible => true,
+                    };
+                    decompose_with_class(ch, compatible, |class, d| self.push_back(class, d));
                 }
-                (None, _) => {
+                None => {
                     if self.buffer.is_empty() {
                         return None;
                     } else {
@@ -157,3 +228,50 @@ impl<I: Iterator<Item=char> + Clone> fmt::Display for Decompositions<I> {
         Ok(())
     }
 }
+
+#[cfg(all(test, not(feature = "alloc")))]
+mod tests {
+    use std::iter;
+    use std::string::{String, ToString};
+    use UnicodeNormalization;
+
+    #[test]
+    fn test_official_without_alloc() {
+        use normalization_tests::NORMALIZATION_TESTS;
+
+        for test in NORMALIZATION_TESTS {
+            assert_eq!(test.source.nfd().collect::<String>(), test.nfd);
+            assert_eq!(test.source.nfkd().collect::<String>(), test.nfkd);
+            assert_eq!(test.source.nfc().collect::<String>(), test.nfc);
+            assert_eq!(test.source.nfkc().collect::<String>(), test.nfkc);
+        }
+    }
+
+    #[test]
+    fn test_long_nonstarter_run() {
+        // Runs up to the limit are reordered as with an allocator.
+        let input: String = iter::once('a')
+            .chain(iter::repeat('\u{301}').take(16))
+            .chain(iter::repeat('\u{316}').take(16))
+            .collect();
+        let expected: String = iter::once('a')
+            .chain(iter::repeat('\u{316}').take(16))
+            .chain(iter::repeat('\u{301}').take(16))
+            .collect();
+        assert_eq!(input.nfd().collect::<String>(), expected);
+        assert_eq!(input.nfc().collect::<String>(),
+                   format!("\u{e1}{}{}", "\u{316}".repeat(16), "\u{301}".repeat(15)));
+
+        // Longer runs can be broken up with `stream_safe` first.
+        let input = "a".to_string() + &"\u{301}".repeat(70);
+        let stream_safe: String = input.stream_safe().collect();
+        assert_eq!(stream_safe.nfd().collect::<String>(), stream_safe);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_nonstarter_run_over_limit() {
+        let input = "a".to_string() + &"\u{301}".repeat(33);
+        input.nfd().count();
+    }
+}
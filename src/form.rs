@@ -0,0 +1,346 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+#[cfg(feature = "alloc")]
+use core::fmt;
+#[cfg(feature = "alloc")]
+use UnicodeNormalization;
+use lookups::{canonical_combining_class, combines_forward};
+use normalize::{
+    Composed,
+    Composer,
+    decompose_canonical,
+    decompose_compatible,
+    hangul_combines_forward,
+    is_hangul_trailing,
+};
+use quick_check::IsNormalized;
+use segments::Segments;
+use lookups;
+
+/// A Unicode normalization form, for APIs that select one at runtime.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Form {
+    /// Normalization Form C (canonical decomposition followed by canonical
+    /// composition).
+    Nfc,
+    /// Normalization Form D (canonical decomposition).
+    Nfd,
+    /// Normalization Form KC (compatibility decomposition followed by
+    /// canonical composition).
+    Nfkc,
+    /// Normalization Form KD (compatibility decomposition).
+    Nfkd,
+}
+
+impl Form {
+    /// The per-character quick check property for this form.
+    #[inline]
+    pub(crate) fn qc(self) -> fn(char) -> IsNormalized {
+        match self {
+            Form::Nfc => lookups::qc_nfc,
+            Form::Nfd => lookups::qc_nfd,
+            Form::Nfkc => lookups::qc_nfkc,
+            Form::Nfkd => lookups::qc_nfkd,
+        }
+    }
+
+    /// Whether normalizing text into this form never affects characters on
+    /// both sides of a position right before `c`, so text can be split there
+    /// and the two halves normalized separately.
+    ///
+    /// This is the case when the decomposition of `c` starts with a starter,
+    /// which for the composed forms must also never compose with a preceding
+    /// character.
+    ///
+    /// ```rust
+    /// use unicode_normalization::Form;
+    ///
+    /// assert!(Form::Nfc.has_boundary_before('e'));
+    /// assert!(!Form::Nfc.has_boundary_before('\u{301}'));
+    /// assert!(!Form::Nfc.has_boundary_before('\u{1161}'));
+    /// assert!(Form::Nfd.has_boundary_before('\u{1161}'));
+    /// ```
+    #[inline]
+    pub fn has_boundary_before(self, c: char) -> bool {
+        if c <= '\x7f' {
+            return true;
+        }
+        let mut first = None;
+        {
+            let emit_char = |d| if first.is_none() { first = Some(d) };
+            match self {
+                Form::Nfc | Form::Nfd => decompose_canonical(c, emit_char),
+                Form::Nfkc | Form::Nfkd => decompose_compatible(c, emit_char),
+            }
+        }
+        let first = first.unwrap_or(c);
+        if canonical_combining_class(first) != 0 {
+            return false;
+        }
+        match self {
+            Form::Nfc | Form::Nfkc => lookups::qc_nfc(first) == IsNormalized::Yes,
+            Form::Nfd | Form::Nfkd => true,
+        }
+    }
+
+    /// Whether normalizing text into this form never affects characters on
+    /// both sides of a position right after `c`.
+    ///
+    /// For the decomposed forms this is the case when the decomposition of
+    /// `c` ends with a starter, or starts with one and ends with a character
+    /// of combining class 1. For the composed forms it must end with a
+    /// starter, and the composition of `c` must not compose with a following
+    /// character, even after composing with a preceding one.
+    ///
+    /// ```rust
+    /// use unicode_normalization::Form;
+    ///
+    /// assert!(Form::Nfd.has_boundary_after('e'));
+    /// assert!(!Form::Nfc.has_boundary_after('e'));
+    /// assert!(Form::Nfc.has_boundary_after('1'));
+    /// assert!(!Form::Nfd.has_boundary_after('\u{e9}'));
+    /// assert!(!Form::Nfc.has_boundary_after('\u{1161}'));
+    /// ```
+    #[inline]
+    pub fn has_boundary_after(self, c: char) -> bool {
+        if c <= '\x7f' {
+            return match self {
+                Form::Nfc | Form::Nfkc => !combines_forward(c),
+                Form::Nfd | Form::Nfkd => true,
+            };
+        }
+        let (first, last, composed) = self.edge_chars(c);
+        let last_ccc = canonical_combining_class(last);
+        match self {
+            Form::Nfc | Form::Nfkc => {
+                last_ccc == 0
+                    && !combines_forward(composed)
+                    && !hangul_combines_forward(composed)
+                    && !may_extend_composition(composed)
+            }
+            Form::Nfd | Form::Nfkd => {
+                // Following characters of class 1 stay after a trailing one,
+                // as long as nothing before it could be reordered after them.
+                last_ccc == 0 || (last_ccc == 1 && canonical_combining_class(first) == 0)
+            }
+        }
+    }
+
+    /// The first and last characters of the decomposition of `c` for this
+    /// form, and the last character left once that decomposition is composed.
+    fn edge_chars(self, c: char) -> (char, char, char) {
+        let mut composer = Composer::new(false);
+        let mut first = None;
+        let mut last = c;
+        let mut composed = c;
+        {
+            let emit_char = |d| {
+                if first.is_none() {
+                    first = Some(d);
+                }
+                last = d;
+                match composer.push(d) {
+                    Composed::Into(composite) => {
+                        if composer.at_starter() {
+                            composed = composite;
+                        }
+                    }
+                    Composed::Starter | Composed::Nonstarter => composed = d,
+                }
+            };
+            match self {
+                Form::Nfc | Form::Nfd => decompose_canonical(c, emit_char),
+                Form::Nfkc | Form::Nfkd => decompose_compatible(c, emit_char),
+            }
+        }
+        (first.unwrap_or(c), last, composed)
+    }
+
+    /// Whether `c` is left alone by normalizing text into this form and
+    /// never interacts with the characters around it, so text can be split
+    /// on both sides of it.
+    ///
+    /// ```rust
+    /// use unicode_normalization::Form;
+    ///
+    /// assert!(Form::Nfc.is_inert('1'));
+    /// assert!(!Form::Nfc.is_inert('e'));
+    /// assert!(!Form::Nfkc.is_inert('\u{fb01}'));
+    /// assert!(Form::Nfc.is_inert('\u{fb01}'));
+    /// ```
+    #[inline]
+    pub fn is_inert(self, c: char) -> bool {
+        self.qc()(c) == IsNormalized::Yes
+            && canonical_combining_class(c) == 0
+            && self.has_boundary_before(c)
+            && self.has_boundary_after(c)
+    }
+
+    /// Whether text can be split between `a` and `b` and the two halves
+    /// normalized separately.
+    #[inline]
+    pub(crate) fn is_boundary_between(self, a: char, b: char) -> bool {
+        self.has_boundary_after(a) || self.has_boundary_before(b)
+    }
+
+    /// Returns the byte offset of the last normalization boundary in `s` at
+    /// or before `offset`.
+    ///
+    /// The start and end of `s` are always boundaries. An offset inside a
+    /// character is rounded down to the start of that character.
+    ///
+    /// ```rust
+    /// use unicode_normalization::Form;
+    ///
+    /// let s = "xe\u{301}y";
+    /// assert_eq!(Form::Nfc.prev_boundary(s, 3), 1);
+    /// assert_eq!(Form::Nfd.prev_boundary(s, 3), 2);
+    /// assert_eq!(Form::Nfc.prev_boundary(s, 4), 4);
+    /// ```
+    pub fn prev_boundary(self, s: &str, offset: usize) -> usize {
+        if offset >= s.len() {
+            return s.len();
+        }
+        let mut i = offset;
+        while !s.is_char_boundary(i) {
+            i -= 1;
+        }
+        let mut next = match s[i..].chars().next() {
+            Some(c) => c,
+            None => return i,
+        };
+        for (j, c) in s[..i].char_indices().rev() {
+            if self.is_boundary_between(c, next) {
+                return i;
+            }
+            i = j;
+            next = c;
+        }
+        0
+    }
+
+    /// Returns the byte offset of the first normalization boundary in `s` at
+    /// or after `offset`.
+    ///
+    /// The start and end of `s` are always boundaries. An offset inside a
+    /// character is rounded up to the end of that character.
+    ///
+    /// ```rust
+    /// use unicode_normalization::Form;
+    ///
+    /// let s = "xe\u{301}y";
+    /// assert_eq!(Form::Nfc.next_boundary(s, 2), 4);
+    /// assert_eq!(Form::Nfc.next_boundary(s, 1), 1);
+    /// assert_eq!(Form::Nfd.next_boundary(s, 3), 4);
+    /// ```
+    pub fn next_boundary(self, s: &str, offset: usize) -> usize {
+        if offset == 0 {
+            return 0;
+        }
+        if offset >= s.len() {
+            return s.len();
+        }
+        let mut i = offset;
+        while !s.is_char_boundary(i) {
+            i += 1;
+        }
+        let mut prev = match s[..i].chars().next_back() {
+            Some(c) => c,
+            None => return i,
+        };
+        for (j, c) in s[i..].char_indices() {
+            if self.is_boundary_between(prev, c) {
+                return i + j;
+            }
+            prev = c;
+        }
+        s.len()
+    }
+
+    /// Returns an iterator over the maximal segments of `s` that
+    /// normalization into this form treats independently.
+    ///
+    /// Each segment starts and ends at a normalization boundary, with no
+    /// boundary in between, so normalizing the segments one by one gives the
+    /// normalization of the whole string.
+    ///
+    /// ```rust
+    /// use unicode_normalization::Form;
+    ///
+    /// let segments: Vec<_> = Form::Nfc.segments("xe\u{301}\u{323}y").collect();
+    /// assert_eq!(segments, ["x", "e\u{301}\u{323}", "y"]);
+    /// ```
+    #[inline]
+    pub fn segments<'a>(self, s: &'a str) -> Segments<'a> {
+        Segments::new(s, self)
+    }
+
+    /// Normalize `iter` into this form, appending the result to `out`.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub(crate) fn extend<I, E>(self, iter: I, out: &mut E)
+        where I: Iterator<Item=char>, E: Extend<char>
+    {
+        match self {
+            Form::Nfc => out.extend(iter.nfc()),
+            Form::Nfd => out.extend(iter.nfd()),
+            Form::Nfkc => out.extend(iter.nfkc()),
+            Form::Nfkd => out.extend(iter.nfkd()),
+        }
+    }
+
+    /// Normalize `iter` into this form, writing the result to `out`.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub(crate) fn write<I, W>(self, iter: I, out: &mut W) -> fmt::Result
+        where I: Iterator<Item=char>, W: fmt::Write
+    {
+        fn write_all<I, W>(iter: I, out: &mut W) -> fmt::Result
+            where I: Iterator<Item=char>, W: fmt::Write
+        {
+            for c in iter {
+                out.write_char(c)?;
+            }
+            Ok(())
+        }
+
+        match self {
+            Form::Nfc => write_all(iter.nfc(), out),
+            Form::Nfd => write_all(iter.nfd(), out),
+            Form::Nfkc => write_all(iter.nfkc(), out),
+            Form::Nfkd => write_all(iter.nfkd(), out),
+        }
+    }
+
+    /// Check whether `iter` is left unchanged by normalizing it into this
+    /// form.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub(crate) fn is_normalized<I>(self, iter: I) -> bool
+        where I: Iterator<Item=char> + Clone
+    {
+        match self {
+            Form::Nfc => iter.clone().eq(iter.nfc()),
+            Form::Nfd => iter.clone().eq(iter.nfd()),
+            Form::Nfkc => iter.clone().eq(iter.nfkc()),
+            Form::Nfkd => iter.clone().eq(iter.nfkd()),
+        }
+    }
+}
+
+/// Whether `c` might compose with a preceding character into one that
+/// composes with a following character, like a Hangul vowel after a leading
+/// consonant. That's assumed of everything that composes with a preceding
+/// character, except the trailing Hangul consonants, which end a syllable.
+#[inline]
+fn may_extend_composition(c: char) -> bool {
+    lookups::qc_nfc(c) == IsNormalized::Maybe && !is_hangul_trailing(c)
+}
@@ -0,0 +1,309 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Hashing of strings that's insensitive to their normalization.
+
+use alloc::string::String;
+use core::borrow::Borrow;
+use core::hash::{Hash, Hasher};
+use core::ops::Deref;
+use core::str;
+use bulk::PlainRuns;
+use compare::canonically_eq;
+use form::Form;
+use quick_check::normalized_prefix_len;
+
+/// Feeds characters into a hasher.
+struct HashSink<'a, H: 'a>(&'a mut H);
+
+impl<'a, H: Hasher> Extend<char> for HashSink<'a, H> {
+    #[inline]
+    fn extend<T: IntoIterator<Item=char>>(&mut self, iter: T) {
+        for c in iter {
+            self.0.write_u32(c as u32);
+        }
+    }
+}
+
+/// Feed `s` normalized into `form` into `state`, without building the
+/// normalized string.
+///
+/// Strings that are equal once normalized hash the same. For NFC and NFD
+/// that's the case for canonically equivalent strings, and for NFKC and NFKD
+/// for compatibility equivalent ones.
+///
+/// ```rust
+/// use std::collections::hash_map::DefaultHasher;
+/// use std::hash::Hasher;
+/// use unicode_normalization::{hash_normalized, Form};
+///
+/// let hash = |s: &str| {
+///     let mut hasher = DefaultHasher::new();
+///     hash_normalized(s, Form::Nfc, &mut hasher);
+///     hasher.finish()
+/// };
+/// assert_eq!(hash("Introducci\u{f3}n"), hash("Introduccio\u{301}n"));
+/// ```
+pub fn hash_normalized<H: Hasher>(s: &str, form: Form, state: &mut H) {
+    write_normalized(s, form, state);
+    // Like `str`, end with a byte that never occurs in the text, so that
+    // hashing several strings in a row doesn't make them run together.
+    state.write_u8(0xff);
+}
+
+/// Feed the characters of `s` normalized into `form` into `state`.
+fn write_normalized<H: Hasher>(s: &str, form: Form, state: &mut H) {
+    let n = normalized_prefix_len(PlainRuns::new(s, form), form.qc()).unwrap_or(s.len());
+    let mut sink = HashSink(state);
+    sink.extend(s[..n].chars());
+    form.extend(s[n..].chars(), &mut sink);
+}
+
+/// A hasher that normalizes the text hashed into it before feeding it to an
+/// inner hasher.
+///
+/// Every `write` of valid UTF-8 is taken to be a whole string, which is how
+/// `str` and `String` hash themselves, and its characters are fed to the
+/// inner hasher normalized, without building the normalized string. Other
+/// writes are passed on as they are. So values that only differ in the
+/// normalization of the strings they contain hash the same, and a string
+/// hashes as `hash_normalized` hashes it.
+///
+/// ```rust
+/// use std::collections::hash_map::DefaultHasher;
+/// use std::hash::{Hash, Hasher};
+/// use unicode_normalization::{Form, NormalizingHasher};
+///
+/// let hash = |key: (&str, u32)| {
+///     let mut hasher = NormalizingHasher::new(DefaultHasher::new(), Form::Nfc);
+///     key.hash(&mut hasher);
+///     hasher.finish()
+/// };
+/// assert_eq!(hash(("Introducci\u{f3}n", 1)), hash(("Introduccio\u{301}n", 1)));
+/// ```
+#[derive(Clone, Debug)]
+pub struct NormalizingHasher<H> {
+    inner: H,
+    form: Form,
+}
+
+impl<H: Hasher> NormalizingHasher<H> {
+    /// Creates a hasher normalizing text into `form` before feeding it to
+    /// `inner`.
+    #[inline]
+    pub fn new(inner: H, form: Form) -> NormalizingHasher<H> {
+        NormalizingHasher { inner, form }
+    }
+
+    /// Gets a reference to the underlying hasher.
+    #[inline]
+    pub fn get_ref(&self) -> &H {
+        &self.inner
+    }
+
+    /// Unwraps this hasher, returning the underlying hasher.
+    #[inline]
+    pub fn into_inner(self) -> H {
+        self.inner
+    }
+}
+
+impl<H: Hasher> Hasher for NormalizingHasher<H> {
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.inner.finish()
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        match str::from_utf8(bytes) {
+            Ok(s) => write_normalized(s, self.form, &mut self.inner),
+            Err(_) => self.inner.write(bytes),
+        }
+    }
+
+    // Integers are passed on directly rather than through `write`, where
+    // their bytes could be taken for text.
+
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.inner.write_u8(i)
+    }
+
+    #[inline]
+    fn write_u16(&mut self, i: u16) {
+        self.inner.write_u16(i)
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.inner.write_u32(i)
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.inner.write_u64(i)
+    }
+
+    #[inline]
+    fn write_u128(&mut self, i: u128) {
+        self.inner.write_u128(i)
+    }
+
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        self.inner.write_usize(i)
+    }
+
+    #[inline]
+    fn write_i8(&mut self, i: i8) {
+        self.inner.write_i8(i)
+    }
+
+    #[inline]
+    fn write_i16(&mut self, i: i16) {
+        self.inner.write_i16(i)
+    }
+
+    #[inline]
+    fn write_i32(&mut self, i: i32) {
+        self.inner.write_i32(i)
+    }
+
+    #[inline]
+    fn write_i64(&mut self, i: i64) {
+        self.inner.write_i64(i)
+    }
+
+    #[inline]
+    fn write_i128(&mut self, i: i128) {
+        self.inner.write_i128(i)
+    }
+
+    #[inline]
+    fn write_isize(&mut self, i: isize) {
+        self.inner.write_isize(i)
+    }
+}
+
+/// An owned string key that's compared and hashed up to canonical
+/// equivalence.
+///
+/// A `HashMap` keyed by `NfcKey` can be looked up with any canonically
+/// equivalent string, through `NfcKeyStr`. The string itself is kept as it
+/// was given.
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use unicode_normalization::{NfcKey, NfcKeyStr};
+///
+/// let mut cache = HashMap::new();
+/// cache.insert(NfcKey::from("Am\u{e9}lie"), 1);
+/// assert_eq!(cache.get(NfcKeyStr::new("Ame\u{301}lie")), Some(&1));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct NfcKey(String);
+
+impl NfcKey {
+    /// Returns the string as it was given.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Unwraps the key, returning the string as it was given.
+    #[inline]
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl From<String> for NfcKey {
+    #[inline]
+    fn from(s: String) -> NfcKey {
+        NfcKey(s)
+    }
+}
+
+impl<'a> From<&'a str> for NfcKey {
+    #[inline]
+    fn from(s: &'a str) -> NfcKey {
+        NfcKey(s.into())
+    }
+}
+
+impl Deref for NfcKey {
+    type Target = NfcKeyStr;
+
+    #[inline]
+    fn deref(&self) -> &NfcKeyStr {
+        NfcKeyStr::new(&self.0)
+    }
+}
+
+impl Borrow<NfcKeyStr> for NfcKey {
+    #[inline]
+    fn borrow(&self) -> &NfcKeyStr {
+        self
+    }
+}
+
+impl PartialEq for NfcKey {
+    #[inline]
+    fn eq(&self, other: &NfcKey) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for NfcKey {}
+
+impl Hash for NfcKey {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+str_wrapper! {
+    /// A borrowed string key that's compared and hashed up to canonical
+    /// equivalence, for looking up `NfcKey`s.
+    #[derive(Debug)]
+    pub struct NfcKeyStr(str);
+}
+
+impl NfcKeyStr {
+    /// Wraps a string slice as a key.
+    #[inline]
+    pub fn new(s: &str) -> &NfcKeyStr {
+        NfcKeyStr::from_str_ref(s)
+    }
+
+    /// Returns the string as it was given.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for NfcKeyStr {
+    #[inline]
+    fn eq(&self, other: &NfcKeyStr) -> bool {
+        canonically_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for NfcKeyStr {}
+
+impl Hash for NfcKeyStr {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_normalized(&self.0, Form::Nfc, state)
+    }
+}
@@ -0,0 +1,150 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Normalization that keeps track of where each character came from.
+
+use core::cmp;
+use core::iter::Peekable;
+use core::ops::Range;
+use core::str::CharIndices;
+use smallvec::SmallVec;
+use form::Form;
+use lookups::canonical_combining_class;
+use normalize::{Composed, Composer, decompose_canonical, decompose_compatible};
+
+/// External iterator for a string's normalization, yielding each character
+/// together with the byte range in the string it came from.
+///
+/// The characters a string's character decomposes into all map to the range
+/// of that character, and reordering moves characters along with their
+/// ranges. A character composed from several others maps to the smallest
+/// range covering all of theirs.
+///
+/// ```rust
+/// use unicode_normalization::{Form, NormalizedIndices};
+///
+/// let nfc: Vec<_> = NormalizedIndices::new("Introduccio\u{301}n", Form::Nfc).skip(10).collect();
+/// assert_eq!(nfc, [('\u{f3}', 10..13), ('n', 13..14)]);
+///
+/// let nfd: Vec<_> = NormalizedIndices::new("\u{e9}", Form::Nfd).collect();
+/// assert_eq!(nfd, [('e', 0..2), ('\u{301}', 0..2)]);
+/// ```
+#[derive(Clone)]
+pub struct NormalizedIndices<'a> {
+    form: Form,
+    iter: Peekable<CharIndices<'a>>,
+
+    // The normalized characters of the current segment, that is of a
+    // character with a normalization boundary before it and everything up to
+    // the next such character.
+    buffer: SmallVec<[(char, Range<usize>); 4]>,
+    pos: usize,
+}
+
+impl<'a> NormalizedIndices<'a> {
+    /// Creates an iterator over `s` normalized into `form`.
+    #[inline]
+    pub fn new(s: &'a str, form: Form) -> NormalizedIndices<'a> {
+        NormalizedIndices {
+            form,
+            iter: s.char_indices().peekable(),
+            buffer: SmallVec::new(),
+            pos: 0,
+        }
+    }
+
+    fn push_decomposed(&mut self, i: usize, c: char) {
+        let range = i..i + c.len_utf8();
+        let buffer = &mut self.buffer;
+        let emit_char = |d| buffer.push((d, range.clone()));
+        match self.form {
+            Form::Nfc | Form::Nfd => decompose_canonical(c, emit_char),
+            Form::Nfkc | Form::Nfkd => decompose_compatible(c, emit_char),
+        }
+    }
+
+    /// Fill the buffer with the next segment in normalized form.
+    fn fill(&mut self) {
+        self.buffer.clear();
+        self.pos = 0;
+        if let Some((i, c)) = self.iter.next() {
+            self.push_decomposed(i, c);
+        }
+        while let Some(&(i, c)) = self.iter.peek() {
+            if self.form.has_boundary_before(c) {
+                break;
+            }
+            self.iter.next();
+            self.push_decomposed(i, c);
+        }
+
+        // Put each run of nonstarters in canonical order.
+        let mut start = 0;
+        for i in 0..self.buffer.len() + 1 {
+            if i == self.buffer.len() || canonical_combining_class(self.buffer[i].0) == 0 {
+                self.buffer[start..i].sort_by_key(|&(c, _)| canonical_combining_class(c));
+                start = i + 1;
+            }
+        }
+
+        if let Form::Nfc | Form::Nfkc = self.form {
+            self.compose();
+        }
+    }
+
+    /// Canonically compose the buffered characters.
+    fn compose(&mut self) {
+        let mut composer = Composer::new(false);
+        let mut starter = 0;
+        let mut len = 0;
+        for i in 0..self.buffer.len() {
+            let (c, range) = self.buffer[i].clone();
+            match composer.push(c) {
+                Composed::Into(composed) => {
+                    let start = cmp::min(self.buffer[starter].1.start, range.start);
+                    let end = cmp::max(self.buffer[starter].1.end, range.end);
+                    self.buffer[starter] = (composed, start..end);
+                    continue;
+                }
+                Composed::Starter => starter = len,
+                Composed::Nonstarter => {}
+            }
+            self.buffer.swap(len, i);
+            len += 1;
+        }
+        self.buffer.truncate(len);
+    }
+}
+
+impl<'a> Iterator for NormalizedIndices<'a> {
+    type Item = (char, Range<usize>);
+
+    #[inline]
+    fn next(&mut self) -> Option<(char, Range<usize>)> {
+        if self.pos == self.buffer.len() {
+            self.fill();
+        }
+        let item = self.buffer.get(self.pos).cloned();
+        self.pos += 1;
+        item
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, _) = self.iter.size_hint();
+        let lower = match self.form {
+            // Decomposition never gives fewer characters than it's given.
+            Form::Nfd | Form::Nfkd => lower,
+            // Composition may merge all that's left into one character.
+            Form::Nfc | Form::Nfkc => cmp::min(lower, 1),
+        };
+        (lower + self.buffer.len().saturating_sub(self.pos), None)
+    }
+}
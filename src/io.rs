@@ -0,0 +1,478 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Streaming normalization of UTF-8 through `std::io` readers and writers.
+
+use std::cmp;
+use std::io::{self, Read, Write};
+use std::str;
+use std::string::String;
+use std::vec::Vec;
+use form::Form;
+use normalizer::Normalizer;
+use utf8::Utf8Error;
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Incremental UTF-8 decoding and normalization shared by the adapters.
+struct State {
+    normalizer: Normalizer,
+    // Bytes of a UTF-8 sequence split across chunks.
+    partial: Vec<u8>,
+    // Number of bytes of input decoded so far, for error offsets.
+    offset: usize,
+}
+
+impl State {
+    fn new(form: Form) -> State {
+        State {
+            normalizer: Normalizer::new(form),
+            partial: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    fn invalid(&self, offset: usize) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, Utf8Error::new(offset))
+    }
+
+    /// Decode `data`, appending everything that's ready to `out`.
+    ///
+    /// If `data` isn't valid UTF-8, nothing of it is consumed.
+    fn push(&mut self, mut data: &[u8], out: &mut String) -> io::Result<()> {
+        // A sequence left over from the last chunk and completed by this
+        // one.
+        let mut completed = [0; 4];
+        let mut completed_len = 0;
+        if !self.partial.is_empty() {
+            let width = match self.partial[0] {
+                0xf0..=0xff => 4,
+                0xe0..=0xef => 3,
+                _ => 2,
+            };
+            let start = self.partial.len();
+            if start + data.len() < width {
+                self.partial.extend_from_slice(data);
+                return Ok(());
+            }
+            completed[..start].copy_from_slice(&self.partial);
+            completed[start..width].copy_from_slice(&data[..width - start]);
+            if str::from_utf8(&completed[..width]).is_err() {
+                return Err(self.invalid(self.offset));
+            }
+            completed_len = width;
+            data = &data[width - start..];
+        }
+
+        let (valid, rest) = match str::from_utf8(data) {
+            Ok(s) => (s, &data[data.len()..]),
+            Err(e) => {
+                let valid_up_to = e.valid_up_to();
+                if e.error_len().is_some() {
+                    return Err(self.invalid(self.offset + completed_len + valid_up_to));
+                }
+                (str::from_utf8(&data[..valid_up_to]).unwrap(), &data[valid_up_to..])
+            }
+        };
+
+        // All of `data` is known to be valid by now.
+        if completed_len > 0 {
+            let s = str::from_utf8(&completed[..completed_len]).unwrap();
+            self.normalizer.push(s, out).unwrap();
+        }
+        self.normalizer.push(valid, out).unwrap();
+        self.partial.clear();
+        self.partial.extend_from_slice(rest);
+        self.offset += completed_len + valid.len();
+        Ok(())
+    }
+
+    /// Append everything still pending to `out`, failing if the input ended
+    /// partway through a UTF-8 sequence.
+    fn finish(&mut self, out: &mut String) -> io::Result<()> {
+        if !self.partial.is_empty() {
+            return Err(self.invalid(self.offset));
+        }
+        self.normalizer.finish(out).unwrap();
+        Ok(())
+    }
+}
+
+/// A reader that normalizes the UTF-8 text read from an inner reader.
+///
+/// Reading fails with `io::ErrorKind::InvalidData` if the inner reader's data
+/// isn't valid UTF-8, with a `Utf8Error` holding the offset into the stream.
+///
+/// ```rust
+/// use std::io::Read;
+/// use unicode_normalization::{Form, NormalizingReader};
+///
+/// let mut reader = NormalizingReader::new("Introduccio\u{301}n".as_bytes(), Form::Nfc);
+/// let mut s = String::new();
+/// reader.read_to_string(&mut s).unwrap();
+/// assert_eq!(s, "Introducci\u{f3}n");
+/// ```
+pub struct NormalizingReader<R> {
+    inner: R,
+    state: State,
+    chunk: Vec<u8>,
+    out: String,
+    pos: usize,
+    done: bool,
+}
+
+impl<R: Read> NormalizingReader<R> {
+    /// Creates a reader normalizing the text from `inner` into `form`.
+    pub fn new(inner: R, form: Form) -> NormalizingReader<R> {
+        NormalizingReader {
+            inner,
+            state: State::new(form),
+            chunk: vec![0; CHUNK_SIZE],
+            out: String::new(),
+            pos: 0,
+            done: false,
+        }
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwraps this reader, returning the underlying reader.
+    ///
+    /// Any data that was read but not yet returned is lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for NormalizingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.pos == self.out.len() {
+            self.out.clear();
+            self.pos = 0;
+            if self.done {
+                return Ok(0);
+            }
+            let n = match self.inner.read(&mut self.chunk) {
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if n == 0 {
+                self.done = true;
+                self.state.finish(&mut self.out)?;
+            } else {
+                self.state.push(&self.chunk[..n], &mut self.out)?;
+            }
+        }
+
+        let ready = &self.out.as_bytes()[self.pos..];
+        let n = cmp::min(buf.len(), ready.len());
+        buf[..n].copy_from_slice(&ready[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// A writer that normalizes UTF-8 text before writing it to an inner writer.
+///
+/// Text is held back until it's known that later writes can't affect its
+/// normalization, so `finish` must be called once all text is written.
+/// Dropping the writer finishes it too, but ignores any errors.
+///
+/// Writing fails with `io::ErrorKind::InvalidData` if the written data isn't
+/// valid UTF-8, with a `Utf8Error` holding the offset into the stream.
+///
+/// Normalized text the inner writer fails to take is kept and written before
+/// anything else. A write that fails doesn't take any of its data, so it can
+/// be retried; if the inner writer only fails after the data has been taken,
+/// the write succeeds and the error is reported by the next call instead.
+///
+/// ```rust
+/// use std::io::Write;
+/// use unicode_normalization::{Form, NormalizingWriter};
+///
+/// let mut writer = NormalizingWriter::new(Vec::new(), Form::Nfc);
+/// writer.write_all(b"Introduccio\xcc").unwrap();
+/// writer.write_all(b"\x81n").unwrap();
+/// let out = writer.into_inner().unwrap();
+/// assert_eq!(out, "Introducci\u{f3}n".as_bytes());
+/// ```
+pub struct NormalizingWriter<W: Write> {
+    inner: Option<W>,
+    state: State,
+    out: String,
+    // Number of bytes of `out` already written.
+    pos: usize,
+}
+
+impl<W: Write> NormalizingWriter<W> {
+    /// Creates a writer normalizing text into `form` before passing it on to
+    /// `inner`.
+    pub fn new(inner: W, form: Form) -> NormalizingWriter<W> {
+        NormalizingWriter {
+            inner: Some(inner),
+            state: State::new(form),
+            out: String::new(),
+            pos: 0,
+        }
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    /// Gets a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().unwrap()
+    }
+
+    /// Writes out all pending text and flushes the underlying writer.
+    ///
+    /// This fails if the text written so far ends partway through a UTF-8
+    /// sequence. Text written afterwards is normalized independently.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.write_out()?;
+        self.state.finish(&mut self.out)?;
+        self.write_out()?;
+        self.get_mut().flush()
+    }
+
+    /// Finishes this writer, returning the underlying writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.finish()?;
+        Ok(self.inner.take().unwrap())
+    }
+
+    /// Write out the normalized text, keeping whatever the inner writer
+    /// doesn't take.
+    fn write_out(&mut self) -> io::Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        while self.pos < self.out.len() {
+            match inner.write(&self.out.as_bytes()[self.pos..]) {
+                Ok(0) => {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "failed to write normalized text"));
+                }
+                Ok(n) => self.pos += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.out.clear();
+        self.pos = 0;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for NormalizingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_out()?;
+        self.state.push(buf, &mut self.out)?;
+        // `buf` is taken by now, so an error is left for the next call to
+        // run into.
+        let _ = self.write_out();
+        Ok(buf.len())
+    }
+
+    /// Writes out the normalized text not yet taken by the underlying writer
+    /// and flushes it.
+    ///
+    /// This doesn't write out text that's still pending, since later writes
+    /// could change its normalization; use `finish` for that.
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_out()?;
+        self.get_mut().flush()
+    }
+}
+
+impl<W: Write> Drop for NormalizingWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NormalizingReader, NormalizingWriter};
+    use std::io::{self, Read, Write};
+    use std::string::String;
+    use std::vec::Vec;
+    use form::Form;
+    use normalization_tests::NORMALIZATION_TESTS;
+    use string::normalize_into;
+    use utf8::Utf8Error;
+
+    /// A writer taking at most two bytes at a time, which fails once on the
+    /// given call.
+    struct Flaky {
+        out: Vec<u8>,
+        calls: usize,
+        fail_at: usize,
+    }
+
+    impl Write for Flaky {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.calls += 1;
+            if self.calls - 1 == self.fail_at {
+                return Err(io::Error::new(io::ErrorKind::Other, "flaky"));
+            }
+            let n = buf.len().min(2);
+            self.out.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A reader handing out a single byte at a time.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+
+    fn offset_of(err: io::Error) -> usize {
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let inner = err.into_inner().unwrap();
+        inner.downcast_ref::<Utf8Error>().unwrap().valid_up_to()
+    }
+
+    #[test]
+    fn test_reader() {
+        let source: String = NORMALIZATION_TESTS.iter().map(|t| t.source).collect();
+        for &form in &[Form::Nfc, Form::Nfd, Form::Nfkc, Form::Nfkd] {
+            let mut expected = String::new();
+            normalize_into(&source, form, &mut expected);
+
+            let mut s = String::new();
+            NormalizingReader::new(source.as_bytes(), form).read_to_string(&mut s).unwrap();
+            assert_eq!(s, expected);
+
+            let mut s = String::new();
+            NormalizingReader::new(Trickle(source.as_bytes()), form).read_to_string(&mut s).unwrap();
+            assert_eq!(s, expected);
+        }
+    }
+
+    #[test]
+    fn test_writer() {
+        let source: String = NORMALIZATION_TESTS.iter().map(|t| t.source).collect();
+        for &form in &[Form::Nfc, Form::Nfd, Form::Nfkc, Form::Nfkd] {
+            let mut expected = String::new();
+            normalize_into(&source, form, &mut expected);
+
+            let mut writer = NormalizingWriter::new(Vec::new(), form);
+            for b in source.as_bytes() {
+                writer.write_all(&[*b]).unwrap();
+            }
+            assert_eq!(writer.into_inner().unwrap(), expected.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_failing_inner_writer() {
+        // Whichever write of the inner writer fails, retrying the failed call
+        // loses and duplicates nothing.
+        let pieces = ["ab", "\u{301}c", "\u{327}", "\u{301}d", "e"];
+        for fail_at in 0..7 {
+            let inner = Flaky { out: Vec::new(), calls: 0, fail_at };
+            let mut writer = NormalizingWriter::new(inner, Form::Nfc);
+            for piece in &pieces {
+                if writer.write_all(piece.as_bytes()).is_err() {
+                    writer.write_all(piece.as_bytes()).unwrap();
+                }
+            }
+            if writer.finish().is_err() {
+                writer.finish().unwrap();
+            }
+            let inner = writer.into_inner().unwrap();
+            assert_eq!(inner.out, "ab\u{301}\u{1e09}de".as_bytes());
+            assert!(inner.calls > fail_at);
+        }
+    }
+
+    #[test]
+    fn test_split_sequence() {
+        // A combining sequence split across writes still gets reordered and
+        // composed.
+        let mut out = Vec::new();
+        {
+            let mut writer = NormalizingWriter::new(&mut out, Form::Nfc);
+            writer.write_all(b"a\xcc").unwrap();
+            writer.write_all(b"\xa3").unwrap();
+            writer.write_all(b"\xcc\x81 x").unwrap();
+        }
+        assert_eq!(out, "\u{1ea1}\u{301} x".as_bytes());
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut s = String::new();
+        let err = NormalizingReader::new(Trickle(b"abc\xe2\x84x"), Form::Nfc)
+            .read_to_string(&mut s)
+            .unwrap_err();
+        assert_eq!(offset_of(err), 3);
+
+        let err = NormalizingReader::new(&b"abc\xe2\x84"[..], Form::Nfd)
+            .read_to_string(&mut s)
+            .unwrap_err();
+        assert_eq!(offset_of(err), 3);
+
+        let mut writer = NormalizingWriter::new(Vec::new(), Form::Nfc);
+        writer.write_all(b"ab").unwrap();
+        let err = writer.write_all(b"c\xff").unwrap_err();
+        assert_eq!(offset_of(err), 3);
+
+        let mut writer = NormalizingWriter::new(Vec::new(), Form::Nfc);
+        writer.write_all(b"ab\xcc").unwrap();
+        assert_eq!(offset_of(writer.finish().unwrap_err()), 2);
+    }
+
+    #[test]
+    fn test_invalid_write_consumes_nothing() {
+        // The sequence completed by the second write is valid, but the byte
+        // after it isn't, so none of the second write may be taken.
+        let mut writer = NormalizingWriter::new(Vec::new(), Form::Nfc);
+        assert_eq!(writer.write(&[0xc3]).unwrap(), 1);
+        assert_eq!(offset_of(writer.write(&[0xa9, 0xff]).unwrap_err()), 2);
+        assert_eq!(offset_of(writer.write(&[0xff]).unwrap_err()), 0);
+        assert_eq!(writer.write(&[0xa9, 0x65]).unwrap(), 2);
+        assert_eq!(writer.into_inner().unwrap(), "\u{e9}e".as_bytes());
+
+        let mut writer = NormalizingWriter::new(Vec::new(), Form::Nfc);
+        writer.write_all(b"a\xe2").unwrap();
+        assert_eq!(offset_of(writer.write(b"\x84\xa6\x80").unwrap_err()), 4);
+        writer.write_all(b"\x84\xa6").unwrap();
+        assert_eq!(writer.into_inner().unwrap(), "a\u{3a9}".as_bytes());
+    }
+}
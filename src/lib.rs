@@ -36,44 +36,194 @@
 //! [dependencies]
 //! unicode-normalization = "0.1.8"
 //! ```
+//!
+//! # `no_std` support
+//!
+//! The crate is `#![no_std]`. The `std` feature, enabled by default, adds the
+//! `std::io` adapters and `std::error::Error` impls. With only the `alloc`
+//! feature the rest of the crate works on top of `alloc`. Without either, the
+//! `char` module, the quick checks and the `nfd`, `nfkd`, `nfc`, `nfkc` and
+//! `fcc` iterators are still available on `core` alone. Those iterators can
+//! only put up to 32 nonstarters in a row in canonical order there, and panic
+//! on longer runs. Stream-safe text never has those, so use `stream_safe`
+//! first on text that might.
 
 #![deny(missing_docs, unsafe_code)]
+#![no_std]
 #![doc(html_logo_url = "https://unicode-rs.github.io/unicode-rs_sm.png",
        html_favicon_url = "https://unicode-rs.github.io/unicode-rs_sm.png")]
 
+#[cfg(any(feature = "std", test))]
+#[macro_use]
+extern crate std;
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "alloc")]
 extern crate smallvec;
+#[cfg(feature = "stream")]
+extern crate futures_core;
+#[cfg(feature = "serde")]
+extern crate serde_crate;
+#[cfg(feature = "rayon")]
+extern crate rayon_crate as rayon;
 
 pub use tables::UNICODE_VERSION;
+#[cfg(feature = "alloc")]
+pub use casefold::Casefold;
+#[cfg(feature = "alloc")]
+pub use caseless::{
+    CanonicalCaseless,
+    CaseFolding,
+    CompatibilityCaseless,
+    canonical_caseless_eq,
+    compatibility_caseless_eq,
+};
+#[cfg(feature = "alloc")]
+pub use compare::{CanonicalOrd, canonical_cmp, canonically_eq, compatibly_eq};
 pub use decompose::Decompositions;
+pub use form::Form;
+#[cfg(feature = "alloc")]
+pub use hash::{NfcKey, NfcKeyStr, NormalizingHasher, hash_normalized};
+#[cfg(feature = "alloc")]
+pub use indices::NormalizedIndices;
+#[cfg(feature = "std")]
+pub use io::{NormalizingReader, NormalizingWriter};
 pub use quick_check::{
     IsNormalized,
-    is_nfc,
+    QuickCheckIssue,
+    QuickCheckReason,
+    check_nfc_quick,
+    check_nfc_stream_safe_quick,
+    check_nfd_quick,
+    check_nfd_stream_safe_quick,
+    check_nfkc_quick,
+    check_nfkd_quick,
+    is_fcd,
+    is_fcd_quick,
     is_nfc_quick,
-    is_nfkc,
     is_nfkc_quick,
-    is_nfc_stream_safe,
+    is_nfkc_casefolded_quick,
+    is_nfkc_simple_casefolded_quick,
     is_nfc_stream_safe_quick,
-    is_nfd,
     is_nfd_quick,
-    is_nfkd,
     is_nfkd_quick,
-    is_nfd_stream_safe,
     is_nfd_stream_safe_quick,
 };
+#[cfg(feature = "alloc")]
+pub use quick_check::{
+    is_nfc,
+    is_nfkc,
+    is_nfkc_casefolded,
+    is_nfkc_simple_casefolded,
+    is_nfc_stream_safe,
+    is_nfd,
+    is_nfkd,
+    is_nfd_stream_safe,
+};
+#[cfg(feature = "alloc")]
+pub use normalized_string::{
+    NfcStr,
+    NfcString,
+    NfdStr,
+    NfdString,
+    NfkcStr,
+    NfkcString,
+    NfkdStr,
+    NfkdString,
+};
+#[cfg(feature = "alloc")]
+pub use normalizer::Normalizer;
+#[cfg(feature = "rayon")]
+pub use parallel::par_normalize;
 pub use recompose::Recompositions;
+pub use segments::Segments;
+#[cfg(feature = "alloc")]
+pub use search::{CanonicalMatches, CanonicalSearcher, find_canonical};
+#[cfg(feature = "stream")]
+pub use stream::NormalizedStream;
 pub use stream_safe::StreamSafe;
-use std::str::Chars;
+#[cfg(feature = "alloc")]
+pub use string::{
+    NormalizeInPlace,
+    nfc_cow,
+    nfd_cow,
+    nfkc_cow,
+    nfkd_cow,
+    normalize_into,
+};
+pub use utf16::{
+    EncodeUtf16,
+    Utf16Chars,
+    Utf16Error,
+    is_normalized_utf16_quick,
+    is_normalized_wtf16_quick,
+};
+#[cfg(feature = "alloc")]
+pub use utf16::{
+    is_normalized_utf16,
+    is_normalized_wtf16,
+    normalize_utf16,
+    normalize_wtf16,
+};
+pub use utf8::{
+    Utf8Error,
+    is_normalized_utf8_quick,
+};
+#[cfg(feature = "alloc")]
+pub use utf8::{
+    is_normalized_utf8,
+    normalize_utf8,
+    normalize_utf8_lossy,
+};
+use core::str::Chars;
 
+#[cfg(feature = "alloc")]
+#[macro_use]
+mod macros;
+
+#[cfg(not(feature = "alloc"))]
+mod array_vec;
+mod bulk;
+#[cfg(feature = "alloc")]
+mod casefold;
+#[cfg(feature = "alloc")]
+mod caseless;
+#[cfg(feature = "alloc")]
+mod compare;
 mod decompose;
+mod form;
+#[cfg(feature = "alloc")]
+mod hash;
+#[cfg(feature = "alloc")]
+mod indices;
+#[cfg(feature = "std")]
+mod io;
 mod lookups;
 mod normalize;
+#[cfg(feature = "alloc")]
+mod normalized_string;
+#[cfg(feature = "alloc")]
+mod normalizer;
+#[cfg(feature = "rayon")]
+mod parallel;
 mod perfect_hash;
 mod recompose;
 mod quick_check;
+#[cfg(feature = "alloc")]
+mod search;
+mod segments;
+#[cfg(feature = "serde")]
+pub mod serde;
+#[cfg(feature = "stream")]
+mod stream;
 mod stream_safe;
+#[cfg(feature = "alloc")]
+mod string;
 mod tables;
+mod utf16;
+mod utf8;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "alloc"))]
 mod test;
 #[cfg(test)]
 mod normalization_tests;
@@ -92,27 +242,57 @@ pub mod char {
 pub trait UnicodeNormalization<I: Iterator<Item=char>> {
     /// Returns an iterator over the string in Unicode Normalization Form D
     /// (canonical decomposition).
-    #[inline]
     fn nfd(self) -> Decompositions<I>;
 
     /// Returns an iterator over the string in Unicode Normalization Form KD
     /// (compatibility decomposition).
-    #[inline]
     fn nfkd(self) -> Decompositions<I>;
 
     /// An Iterator over the string in Unicode Normalization Form C
     /// (canonical decomposition followed by canonical composition).
-    #[inline]
     fn nfc(self) -> Recompositions<I>;
 
     /// An Iterator over the string in Unicode Normalization Form KC
     /// (compatibility decomposition followed by canonical composition).
-    #[inline]
     fn nfkc(self) -> Recompositions<I>;
 
+    /// An Iterator over the string in "Fast C Contiguous" form, as described
+    /// in [Unicode Technical Note #5](http://www.unicode.org/notes/tn5/#FCC).
+    ///
+    /// This is NFC, except that any character left between a starter and a
+    /// following character blocks them from composing, so only contiguous
+    /// sequences compose.
+    fn fcc(self) -> Recompositions<I>;
+
+    /// An Iterator over the string transformed by `toNFKC_Casefold`: each
+    /// character of its canonical decomposition is replaced by its
+    /// NFKC_Casefold mapping, which removes case and compatibility
+    /// differences as well as default ignorable characters, and the result
+    /// is put in NFC.
+    #[cfg(feature = "alloc")]
+    fn nfkc_casefold(self) -> Recompositions<Casefold<I>>;
+
+    /// An Iterator over the string transformed like `nfkc_casefold`, but
+    /// using simple case folding, so characters like U+00DF LATIN SMALL
+    /// LETTER SHARP S never fold to multiple characters.
+    #[cfg(feature = "alloc")]
+    fn nfkc_simple_casefold(self) -> Recompositions<Casefold<I>>;
+
+    /// An Iterator over the key compared by canonical caseless matching
+    /// (D145): the string is decomposed, case folded with `folding` and
+    /// decomposed again.
+    #[cfg(feature = "alloc")]
+    fn canonical_caseless(self, folding: CaseFolding) -> CanonicalCaseless<I>;
+
+    /// An Iterator over the key compared by compatibility caseless matching
+    /// (D146): like `canonical_caseless`, but additionally followed by
+    /// compatibility decomposition and another round of case folding, which
+    /// some compatibility decompositions need.
+    #[cfg(feature = "alloc")]
+    fn compatibility_caseless(self, folding: CaseFolding) -> CompatibilityCaseless<I>;
+
     /// An Iterator over the string with Conjoining Grapheme Joiner characters
     /// inserted according to the Stream-Safe Text Process (UAX15-D4)
-    #[inline]
     fn stream_safe(self) -> StreamSafe<I>;
 }
 
@@ -137,6 +317,35 @@ impl<'a> UnicodeNormalization<Chars<'a>> for &'a str {
         recompose::new_compatible(self.chars())
     }
 
+    #[inline]
+    fn fcc(self) -> Recompositions<Chars<'a>> {
+        recompose::new_contiguous(self.chars())
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn nfkc_casefold(self) -> Recompositions<Casefold<Chars<'a>>> {
+        recompose::new_canonical(casefold::new_full(self.chars()))
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn nfkc_simple_casefold(self) -> Recompositions<Casefold<Chars<'a>>> {
+        recompose::new_canonical(casefold::new_simple(self.chars()))
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn canonical_caseless(self, folding: CaseFolding) -> CanonicalCaseless<Chars<'a>> {
+        caseless::new_canonical(self.chars(), folding)
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn compatibility_caseless(self, folding: CaseFolding) -> CompatibilityCaseless<Chars<'a>> {
+        caseless::new_compatible(self.chars(), folding)
+    }
+
     #[inline]
     fn stream_safe(self) -> StreamSafe<Chars<'a>> {
         StreamSafe::new(self.chars())
@@ -164,6 +373,35 @@ impl<I: Iterator<Item=char>> UnicodeNormalization<I> for I {
         recompose::new_compatible(self)
     }
 
+    #[inline]
+    fn fcc(self) -> Recompositions<I> {
+        recompose::new_contiguous(self)
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn nfkc_casefold(self) -> Recompositions<Casefold<I>> {
+        recompose::new_canonical(casefold::new_full(self))
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn nfkc_simple_casefold(self) -> Recompositions<Casefold<I>> {
+        recompose::new_canonical(casefold::new_simple(self))
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn canonical_caseless(self, folding: CaseFolding) -> CanonicalCaseless<I> {
+        caseless::new_canonical(self, folding)
+    }
+
+    #[cfg(feature = "alloc")]
+    #[inline]
+    fn compatibility_caseless(self, folding: CaseFolding) -> CompatibilityCaseless<I> {
+        caseless::new_compatible(self, folding)
+    }
+
     #[inline]
     fn stream_safe(self) -> StreamSafe<I> {
         StreamSafe::new(self)
@@ -8,72 +8,213 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-//! Lookups of unicode properties using minimal perfect hashing.
+//! Lookups of unicode properties.
+//!
+//! The normalization properties of a character are packed into one record,
+//! found with a single lookup in a three stage trie. The case folding
+//! mappings use minimal perfect hashing.
+//!
+//! The case folding mappings stay out of the trie on purpose. Every bit of a
+//! `Properties` record is taken, so they'd need wider records or a second
+//! leaf table. Only the case folding iterators and quick checks read them,
+//! while every normalization lookup would pay for the larger trie.
 
 use perfect_hash::mph_lookup;
+use quick_check::IsNormalized;
 use tables::*;
 
+/// The normalization properties of a character, packed into a `u64` as laid
+/// out in `scripts/unicode.py`.
+#[derive(Clone, Copy)]
+pub(crate) struct Properties(u64);
+
+impl Properties {
+    #[inline]
+    pub(crate) fn combining_class(self) -> u8 {
+        self.0 as u8
+    }
+
+    #[inline]
+    fn qc(self, shift: u32) -> IsNormalized {
+        match (self.0 >> shift) & 3 {
+            0 => IsNormalized::Yes,
+            1 => IsNormalized::No,
+            _ => IsNormalized::Maybe,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn qc_nfc(self) -> IsNormalized {
+        self.qc(8)
+    }
+
+    #[inline]
+    pub(crate) fn qc_nfkc(self) -> IsNormalized {
+        self.qc(10)
+    }
+
+    #[inline]
+    fn qc_decomposed(self, bit: u32) -> IsNormalized {
+        // The decomposed forms have no Maybe values.
+        if self.0 & 1 << bit != 0 { IsNormalized::No } else { IsNormalized::Yes }
+    }
+
+    #[inline]
+    pub(crate) fn qc_nfd(self) -> IsNormalized {
+        self.qc_decomposed(12)
+    }
+
+    #[inline]
+    pub(crate) fn qc_nfkd(self) -> IsNormalized {
+        self.qc_decomposed(13)
+    }
+
+    #[inline]
+    pub(crate) fn is_combining_mark(self) -> bool {
+        self.0 & 1 << 14 != 0
+    }
+
+    #[inline]
+    pub(crate) fn trailing_nonstarters(self) -> usize {
+        (self.0 >> 15) as usize & 3
+    }
+
+    #[inline]
+    fn decomposed_chars(self, skip: usize, len: usize) -> &'static [char] {
+        let start = (self.0 >> 32) as u16 as usize + skip;
+        &DECOMPOSED_CHARS[start..start + len]
+    }
+
+    #[inline]
+    pub(crate) fn canonical_decomposition(self) -> Option<&'static [char]> {
+        match (self.0 >> 17) as usize & 7 {
+            0 => None,
+            len => Some(self.decomposed_chars(0, len)),
+        }
+    }
+
+    /// The compatibility decomposition, if it differs from the canonical one.
+    #[inline]
+    pub(crate) fn compatibility_decomposition(self) -> Option<&'static [char]> {
+        match (self.0 >> 20) as usize & 0x1f {
+            0 => None,
+            len => Some(self.decomposed_chars((self.0 >> 17) as usize & 7, len)),
+        }
+    }
+
+    /// The full decomposition, either the compatibility one or the canonical
+    /// one.
+    #[inline]
+    pub(crate) fn decomposition(self, compatible: bool) -> Option<&'static [char]> {
+        let compatibility = if compatible { self.compatibility_decomposition() } else { None };
+        compatibility.or_else(|| self.canonical_decomposition())
+    }
+
+    /// The second characters of the pairs with a primary composite starting
+    /// with this character, along with the composites.
+    #[inline]
+    pub(crate) fn compositions(self) -> &'static [(char, char)] {
+        let start = (self.0 >> 48) as usize;
+        let len = (self.0 >> 25) as usize & 0x7f;
+        &COMPOSITIONS[start..start + len]
+    }
+}
+
+/// Look up the normalization properties of a character.
+#[inline]
+pub(crate) fn properties(c: char) -> Properties {
+    let cp = c as u32;
+    let middle_mask = (1 << (TRIE_INDEX_SHIFT - TRIE_MIDDLE_SHIFT)) - 1;
+    let leaf_mask = (1 << TRIE_MIDDLE_SHIFT) - 1;
+    let middle = TRIE_INDEX[(cp >> TRIE_INDEX_SHIFT) as usize] as usize;
+    let leaf = TRIE_MIDDLE[middle + ((cp >> TRIE_MIDDLE_SHIFT) & middle_mask) as usize] as usize;
+    Properties(TRIE_LEAVES[leaf + (cp & leaf_mask) as usize])
+}
+
 /// Look up the canonical combining class for a codepoint.
 /// 
 /// The value returned is as defined in the Unicode Character Database.
+#[inline]
 pub fn canonical_combining_class(c: char) -> u8 {
-    mph_lookup(c.into(), CANONICAL_COMBINING_CLASS_SALT, CANONICAL_COMBINING_CLASS_KV,
-        u8_lookup_fk, u8_lookup_fv, 0)
+    properties(c).combining_class()
 }
 
+#[inline]
 pub(crate) fn composition_table(c1: char, c2: char) -> Option<char> {
-    if c1 < '\u{10000}' && c2 < '\u{10000}' {
-        mph_lookup((c1 as u32) << 16 | (c2 as u32),
-        COMPOSITION_TABLE_SALT, COMPOSITION_TABLE_KV,
-        pair_lookup_fk, pair_lookup_fv_opt, None)
-    } else {
-        composition_table_astral(c1, c2)
-    }
+    properties(c1).compositions().iter()
+        .find(|&&(second, _)| second == c2)
+        .map(|&(_, composed)| composed)
 }
 
+#[inline]
 pub(crate) fn canonical_fully_decomposed(c: char) -> Option<&'static [char]> {
-    mph_lookup(c.into(), CANONICAL_DECOMPOSED_SALT, CANONICAL_DECOMPOSED_KV,
-        pair_lookup_fk, pair_lookup_fv_opt, None)
-}
-
-pub(crate) fn compatibility_fully_decomposed(c: char) -> Option<&'static [char]> {
-    mph_lookup(c.into(), COMPATIBILITY_DECOMPOSED_SALT, COMPATIBILITY_DECOMPOSED_KV,
-        pair_lookup_fk, pair_lookup_fv_opt, None)
+    properties(c).canonical_decomposition()
 }
 
 /// Return whether the given character is a combining mark (`General_Category=Mark`)
+#[inline]
 pub fn is_combining_mark(c: char) -> bool {
-    mph_lookup(c.into(), COMBINING_MARK_SALT, COMBINING_MARK_KV,
-        bool_lookup_fk, bool_lookup_fv, false)
+    properties(c).is_combining_mark()
 }
 
-pub fn stream_safe_trailing_nonstarters(c: char) -> usize {
-    mph_lookup(c.into(), TRAILING_NONSTARTERS_SALT, TRAILING_NONSTARTERS_KV,
-        u8_lookup_fk, u8_lookup_fv, 0) as usize
+/// Return whether the given character is the first of a pair of characters
+/// with a primary composite, not counting Hangul.
+#[inline]
+pub(crate) fn combines_forward(c: char) -> bool {
+    !properties(c).compositions().is_empty()
 }
 
-/// Extract the key in a 24 bit key and 8 bit value packed in a u32.
 #[inline]
-fn u8_lookup_fk(kv: u32) -> u32 {
-    kv >> 8
+pub(crate) fn qc_nfc(c: char) -> IsNormalized {
+    properties(c).qc_nfc()
 }
 
-/// Extract the value in a 24 bit key and 8 bit value packed in a u32.
 #[inline]
-fn u8_lookup_fv(kv: u32) -> u8 {
-    (kv & 0xff) as u8
+pub(crate) fn qc_nfkc(c: char) -> IsNormalized {
+    properties(c).qc_nfkc()
 }
 
-/// Extract the key for a boolean lookup.
 #[inline]
-fn bool_lookup_fk(kv: u32) -> u32 {
-    kv
+pub(crate) fn qc_nfd(c: char) -> IsNormalized {
+    properties(c).qc_nfd()
 }
 
-/// Extract the value for a boolean lookup.
 #[inline]
-fn bool_lookup_fv(_kv: u32) -> bool {
-    true
+pub(crate) fn qc_nfkd(c: char) -> IsNormalized {
+    properties(c).qc_nfkd()
+}
+
+pub(crate) fn nfkc_casefold(c: char) -> Option<&'static [char]> {
+    mph_lookup(c.into(), NFKC_CASEFOLD_SALT, NFKC_CASEFOLD_KV,
+        pair_lookup_fk, pair_lookup_fv_opt, None)
+}
+
+pub(crate) fn nfkc_simple_casefold(c: char) -> Option<&'static [char]> {
+    // Only the mappings that differ from NFKC_Casefold are stored.
+    mph_lookup(c.into(), NFKC_SIMPLE_CASEFOLD_SALT, NFKC_SIMPLE_CASEFOLD_KV,
+        pair_lookup_fk, pair_lookup_fv_opt, None)
+        .or_else(|| nfkc_casefold(c))
+}
+
+#[cfg(feature = "alloc")]
+pub(crate) fn case_folding(c: char) -> Option<&'static [char]> {
+    mph_lookup(c.into(), CASE_FOLDING_SALT, CASE_FOLDING_KV,
+        pair_lookup_fk, pair_lookup_fv_opt, None)
+}
+
+/// Return whether the given character is changed by the NFKC_Casefold
+/// mapping (`Changes_When_NFKC_Casefolded`).
+pub(crate) fn changes_when_nfkc_casefolded(c: char) -> bool {
+    nfkc_casefold(c).is_some()
+}
+
+/// Return whether the given character is changed by the NFKC_SimpleCasefold
+/// mapping.
+pub(crate) fn changes_when_nfkc_simple_casefolded(c: char) -> bool {
+    match nfkc_simple_casefold(c) {
+        Some(mapping) => mapping != [c],
+        None => false,
+    }
 }
 
 /// Extract the key in a pair.
@@ -87,3 +228,42 @@ fn pair_lookup_fk<T>(kv: (u32, T)) -> u32 {
 fn pair_lookup_fv_opt<T>(kv: (u32, T)) -> Option<T> {
     Some(kv.1)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{canonical_combining_class, composition_table, properties, qc_nfc, qc_nfd, qc_nfkd};
+    use std::char;
+    use normalize::is_hangul_syllable;
+    use quick_check::IsNormalized;
+
+    #[test]
+    fn test_properties() {
+        assert_eq!(canonical_combining_class('\u{301}'), 230);
+        assert_eq!(canonical_combining_class('\u{1d16e}'), 216);
+        assert_eq!(properties('\u{1e0a}').decomposition(false), Some(&['D', '\u{307}'][..]));
+        assert_eq!(properties('\u{fb01}').decomposition(false), None);
+        assert_eq!(properties('\u{fb01}').decomposition(true), Some(&['f', 'i'][..]));
+        assert_eq!(properties('\u{1e9b}').decomposition(true), Some(&['s', '\u{307}'][..]));
+        assert_eq!(composition_table('A', '\u{30a}'), Some('\u{c5}'));
+        assert_eq!(composition_table('\u{11099}', '\u{110ba}'), Some('\u{1109a}'));
+        assert_eq!(composition_table('\u{30a}', 'A'), None);
+        assert_eq!(properties('\u{10ffff}').0, 0);
+    }
+
+    #[test]
+    fn test_properties_agree() {
+        for c in (0..0x110000).filter_map(char::from_u32) {
+            let props = properties(c);
+            let decomposes = is_hangul_syllable(c) || props.decomposition(false).is_some();
+            assert_eq!(qc_nfd(c) == IsNormalized::No, decomposes, "{:?}", c);
+            let decomposes = is_hangul_syllable(c) || props.decomposition(true).is_some();
+            assert_eq!(qc_nfkd(c) == IsNormalized::No, decomposes, "{:?}", c);
+            let first = [c];
+            let first = props.decomposition(false).unwrap_or(&first);
+            for &(second, composed) in props.compositions() {
+                assert_eq!(qc_nfc(second), IsNormalized::Maybe);
+                assert!(properties(composed).decomposition(false).unwrap().starts_with(first));
+            }
+        }
+    }
+}
@@ -0,0 +1,33 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+/// Declares a `#[repr(transparent)]` wrapper around `str`, along with a
+/// private `from_str_ref` that wraps a string slice.
+///
+/// This is the one place the crate casts `&str` to a wrapper type, so that
+/// the cast can't be separated from the `repr` it relies on.
+macro_rules! str_wrapper {
+    ($(#[$attr:meta])* pub struct $name:ident(str);) => {
+        $(#[$attr])*
+        #[repr(transparent)]
+        pub struct $name(str);
+
+        impl $name {
+            #[allow(unsafe_code)]
+            #[inline]
+            fn from_str_ref(s: &str) -> &$name {
+                // SAFETY: `$name` is a `#[repr(transparent)]` wrapper around
+                // `str`, so `*const str` and `*const $name` have the same
+                // layout and metadata, and the result borrows from `s`.
+                unsafe { &*(s as *const str as *const $name) }
+            }
+        }
+    }
+}
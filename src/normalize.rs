@@ -9,9 +9,14 @@
 // except according to those terms.
 
 //! Functions for computing canonical and compatible decompositions for Unicode characters.
-use std::char;
-use std::ops::FnMut;
-use lookups::{canonical_fully_decomposed, composition_table, compatibility_fully_decomposed};
+use core::char;
+use core::ops::FnMut;
+use lookups::{
+    canonical_combining_class,
+    canonical_fully_decomposed,
+    composition_table,
+    properties,
+};
 
 /// Compute canonical Unicode decomposition for character.
 /// See [Unicode Standard Annex #15](http://www.unicode.org/reports/tr15/)
@@ -26,9 +31,7 @@ pub fn decompose_canonical<F>(c: char, emit_char: F) where F: FnMut(char) {
 /// for more information.
 #[inline]
 pub fn decompose_compatible<F: FnMut(char)>(c: char, emit_char: F) {
-    let decompose_char = |c| compatibility_fully_decomposed(c)
-        .or_else(|| canonical_fully_decomposed(c));
-    decompose(c, decompose_char, emit_char)
+    decompose(c, |c| properties(c).decomposition(true), emit_char)
 }
 
 #[inline]
@@ -58,6 +61,30 @@ fn decompose<D, F>(c: char, decompose_char: D, mut emit_char: F)
     emit_char(c);
 }
 
+/// Like `decompose_canonical` or `decompose_compatible`, but passing the
+/// canonical combining class of each character along. A character that
+/// doesn't decompose takes a single table lookup.
+#[inline]
+pub(crate) fn decompose_with_class<F>(c: char, compatible: bool, mut emit: F)
+    where F: FnMut(u8, char)
+{
+    // Jamo are all starters.
+    if is_hangul_syllable(c) {
+        decompose_hangul(c, |d| emit(0, d));
+        return;
+    }
+
+    let props = properties(c);
+    match props.decomposition(compatible) {
+        Some(decomposed) => {
+            for &d in decomposed {
+                emit(properties(d).combining_class(), d);
+            }
+        }
+        None => emit(props.combining_class(), c),
+    }
+}
+
 /// Compose two characters into a single character, if possible.
 /// See [Unicode Standard Annex #15](http://www.unicode.org/reports/tr15/)
 /// for more information.
@@ -65,6 +92,85 @@ pub fn compose(a: char, b: char) -> Option<char> {
     compose_hangul(a, b).or_else(|| composition_table(a, b))
 }
 
+/// What became of a character fed to a `Composer`.
+pub(crate) enum Composed {
+    /// It composed with the last starter into this character, which is the
+    /// starter now.
+    Into(char),
+    /// It's a starter that was left as is, and the starter now.
+    Starter,
+    /// It's a nonstarter that was left as is.
+    Nonstarter,
+}
+
+/// Canonical composition of a decomposed and canonically ordered sequence
+/// fed one character at a time, keeping track of the last starter and of
+/// whether the next character is blocked from it.
+#[derive(Clone)]
+pub(crate) struct Composer {
+    starter: Option<char>,
+    // The combining class of the last character left after the starter, if
+    // any.
+    last_ccc: Option<u8>,
+    // Whether only characters right next to the starter may compose with it,
+    // as in FCC.
+    contiguous: bool,
+}
+
+impl Composer {
+    #[inline]
+    pub(crate) fn new(contiguous: bool) -> Composer {
+        Composer { starter: None, last_ccc: None, contiguous }
+    }
+
+    /// The last starter, composed with everything that composed with it.
+    #[inline]
+    pub(crate) fn starter(&self) -> Option<char> {
+        self.starter
+    }
+
+    #[inline]
+    pub(crate) fn take_starter(&mut self) -> Option<char> {
+        self.starter.take()
+    }
+
+    /// Whether nothing has been left after the last starter.
+    #[inline]
+    pub(crate) fn at_starter(&self) -> bool {
+        self.last_ccc.is_none()
+    }
+
+    #[inline]
+    pub(crate) fn push(&mut self, c: char) -> Composed {
+        // Characters below U+0300 are starters and never compose with the
+        // character before them.
+        if c < '\u{300}' {
+            self.starter = Some(c);
+            self.last_ccc = None;
+            return Composed::Starter;
+        }
+        let ccc = canonical_combining_class(c);
+        if let Some(s) = self.starter {
+            let blocked = match self.last_ccc {
+                Some(last_ccc) => self.contiguous || last_ccc >= ccc,
+                None => false,
+            };
+            if let Some(composed) = if blocked { None } else { compose(s, c) } {
+                self.starter = Some(composed);
+                return Composed::Into(composed);
+            }
+        }
+        if ccc == 0 {
+            self.starter = Some(c);
+            self.last_ccc = None;
+            Composed::Starter
+        } else {
+            self.last_ccc = Some(ccc);
+            Composed::Nonstarter
+        }
+    }
+}
+
 // Constants from Unicode 9.0.0 Section 3.12 Conjoining Jamo Behavior
 // http://www.unicode.org/versions/Unicode9.0.0/ch03.pdf#M9.32468.Heading.310.Combining.Jamo.Behavior
 const S_BASE: u32 = 0xAC00;
@@ -74,8 +180,8 @@ const T_BASE: u32 = 0x11A7;
 const L_COUNT: u32 = 19;
 const V_COUNT: u32 = 21;
 const T_COUNT: u32 = 28;
-const N_COUNT: u32 = (V_COUNT * T_COUNT);
-const S_COUNT: u32 = (L_COUNT * N_COUNT);
+const N_COUNT: u32 = V_COUNT * T_COUNT;
+const S_COUNT: u32 = L_COUNT * N_COUNT;
 
 const S_LAST: u32 = S_BASE + S_COUNT - 1;
 const L_LAST: u32 = L_BASE + L_COUNT - 1;
@@ -90,6 +196,25 @@ pub(crate) fn is_hangul_syllable(c: char) -> bool {
     (c as u32) >= S_BASE && (c as u32) < (S_BASE + S_COUNT)
 }
 
+/// Whether the given character is the first of a pair of Hangul characters
+/// that compose: a leading consonant, or an LV syllable.
+pub(crate) fn hangul_combines_forward(c: char) -> bool {
+    match c as u32 {
+        L_BASE..=L_LAST => true,
+        S_BASE..=S_LAST => (c as u32 - S_BASE) % T_COUNT == 0,
+        _ => false,
+    }
+}
+
+/// Whether the given character is a trailing Hangul consonant, which only
+/// composes as the last part of an LVT syllable.
+pub(crate) fn is_hangul_trailing(c: char) -> bool {
+    match c as u32 {
+        T_FIRST..=T_LAST => true,
+        _ => false,
+    }
+}
+
 // Decompose a precomposed Hangul syllable
 #[allow(unsafe_code)]
 #[inline(always)]
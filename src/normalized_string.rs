@@ -0,0 +1,298 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! String types that are guaranteed to be normalized.
+
+use alloc::borrow::{Borrow, ToOwned};
+use alloc::string::String;
+use core::fmt;
+use core::iter::FromIterator;
+use core::ops::Deref;
+use form::Form;
+use quick_check::{is_nfc, is_nfd, is_nfkc, is_nfkd};
+use string::{NormalizeInPlace, normalize_into};
+
+/// Append `s` to the normalized string `out`, renormalizing only the end of
+/// `out` that `s` might affect.
+fn push_normalized(out: &mut String, s: &str, form: Form) {
+    let affects_out = s.chars().next().map_or(false, |c| !form.has_boundary_before(c));
+    if !affects_out {
+        normalize_into(s, form, out);
+        return;
+    }
+    let i = out.char_indices()
+        .rev()
+        .find(|&(_, c)| form.has_boundary_before(c))
+        .map_or(0, |(i, _)| i);
+    let mut tail = String::with_capacity(out.len() - i + s.len());
+    tail.push_str(&out[i..]);
+    tail.push_str(s);
+    out.truncate(i);
+    normalize_into(&tail, form, out);
+}
+
+macro_rules! normalized_string {
+    ($(#[$string_attr:meta])* $string:ident,
+     $(#[$str_attr:meta])* $str:ident,
+     $form:expr, $is_normalized:ident) => {
+        $(#[$string_attr])*
+        #[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $string(String);
+
+        impl $string {
+            /// Creates an empty string.
+            #[inline]
+            pub fn new() -> $string {
+                $string(String::new())
+            }
+
+            /// Wraps `s` if it's already normalized, and returns it back
+            /// otherwise.
+            #[inline]
+            pub fn from_normalized(s: String) -> Result<$string, String> {
+                if $is_normalized(&s) {
+                    Ok($string(s))
+                } else {
+                    Err(s)
+                }
+            }
+
+            /// Returns the string as a string slice.
+            #[inline]
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            /// Unwraps the normalized string.
+            #[inline]
+            pub fn into_string(self) -> String {
+                self.0
+            }
+
+            /// Appends `s`, normalizing it along with the end of this string
+            /// where needed.
+            #[inline]
+            pub fn push_str(&mut self, s: &str) {
+                push_normalized(&mut self.0, s, $form);
+            }
+
+            /// Appends a character, normalizing it along with the end of
+            /// this string where needed.
+            #[inline]
+            pub fn push(&mut self, c: char) {
+                let mut buf = [0; 4];
+                self.push_str(c.encode_utf8(&mut buf));
+            }
+        }
+
+        impl Deref for $string {
+            type Target = $str;
+
+            #[inline]
+            fn deref(&self) -> &$str {
+                $str::from_str_ref(&self.0)
+            }
+        }
+
+        impl Borrow<$str> for $string {
+            #[inline]
+            fn borrow(&self) -> &$str {
+                self
+            }
+        }
+
+        impl AsRef<str> for $string {
+            #[inline]
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl<'a> From<&'a str> for $string {
+            /// Normalizes a string slice.
+            #[inline]
+            fn from(s: &'a str) -> $string {
+                let mut out = String::with_capacity(s.len());
+                normalize_into(s, $form, &mut out);
+                $string(out)
+            }
+        }
+
+        impl From<String> for $string {
+            /// Normalizes a string, reusing its allocation.
+            #[inline]
+            fn from(mut s: String) -> $string {
+                s.normalize_in_place($form);
+                $string(s)
+            }
+        }
+
+        impl<'a> From<&'a $str> for $string {
+            #[inline]
+            fn from(s: &'a $str) -> $string {
+                $string(s.0.into())
+            }
+        }
+
+        impl From<$string> for String {
+            #[inline]
+            fn from(s: $string) -> String {
+                s.0
+            }
+        }
+
+        impl Extend<char> for $string {
+            fn extend<T: IntoIterator<Item=char>>(&mut self, iter: T) {
+                let s: String = iter.into_iter().collect();
+                self.push_str(&s);
+            }
+        }
+
+        impl<'a> Extend<&'a str> for $string {
+            fn extend<T: IntoIterator<Item=&'a str>>(&mut self, iter: T) {
+                let s: String = iter.into_iter().collect();
+                self.push_str(&s);
+            }
+        }
+
+        impl FromIterator<char> for $string {
+            fn from_iter<T: IntoIterator<Item=char>>(iter: T) -> $string {
+                let s: String = iter.into_iter().collect();
+                $string::from(s)
+            }
+        }
+
+        impl fmt::Debug for $string {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Debug::fmt(&self.0, f)
+            }
+        }
+
+        impl fmt::Display for $string {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)
+            }
+        }
+
+        str_wrapper! {
+            $(#[$str_attr])*
+            #[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+            pub struct $str(str);
+        }
+
+        impl $str {
+            /// Wraps `s` if it's already normalized.
+            #[inline]
+            pub fn new(s: &str) -> Option<&$str> {
+                if $is_normalized(s) {
+                    Some($str::from_str_ref(s))
+                } else {
+                    None
+                }
+            }
+
+            /// Returns the string as a string slice.
+            #[inline]
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl Deref for $str {
+            type Target = str;
+
+            #[inline]
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl AsRef<str> for $str {
+            #[inline]
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl ToOwned for $str {
+            type Owned = $string;
+
+            #[inline]
+            fn to_owned(&self) -> $string {
+                $string::from(self)
+            }
+        }
+
+        impl fmt::Debug for $str {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Debug::fmt(&self.0, f)
+            }
+        }
+
+        impl fmt::Display for $str {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                fmt::Display::fmt(&self.0, f)
+            }
+        }
+    }
+}
+
+normalized_string! {
+    /// An owned string that's always in NFC.
+    ///
+    /// Converting from a string normalizes it, and appending to it
+    /// renormalizes only the text around the join.
+    ///
+    /// ```rust
+    /// use unicode_normalization::NfcString;
+    ///
+    /// let mut s = NfcString::from("Introduccio");
+    /// s.push('\u{301}');
+    /// s.push_str("n");
+    /// assert_eq!(s.as_str(), "Introducci\u{f3}n");
+    /// ```
+    NfcString,
+    /// A string slice that's always in NFC.
+    NfcStr,
+    Form::Nfc, is_nfc
+}
+
+normalized_string! {
+    /// An owned string that's always in NFD.
+    ///
+    /// Converting from a string normalizes it, and appending to it
+    /// renormalizes only the text around the join.
+    NfdString,
+    /// A string slice that's always in NFD.
+    NfdStr,
+    Form::Nfd, is_nfd
+}
+
+normalized_string! {
+    /// An owned string that's always in NFKC.
+    ///
+    /// Converting from a string normalizes it, and appending to it
+    /// renormalizes only the text around the join.
+    NfkcString,
+    /// A string slice that's always in NFKC.
+    NfkcStr,
+    Form::Nfkc, is_nfkc
+}
+
+normalized_string! {
+    /// An owned string that's always in NFKD.
+    ///
+    /// Converting from a string normalizes it, and appending to it
+    /// renormalizes only the text around the join.
+    NfkdString,
+    /// A string slice that's always in NFKD.
+    NfkdStr,
+    Form::Nfkd, is_nfkd
+}
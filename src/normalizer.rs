@@ -0,0 +1,104 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use alloc::string::String;
+use core::fmt;
+use form::Form;
+use string::write_normalized;
+
+/// Incremental normalizer for text that arrives in pieces.
+///
+/// Each pushed piece of text is normalized as far as possible and written to
+/// a sink. Text from the last normalization boundary onwards is held back,
+/// since characters pushed later might still reorder or compose with it, and
+/// is only written out by `finish`.
+///
+/// If the sink returns an error, the normalizer is left as it was before the
+/// call that failed, which can be retried. Part of the text may have been
+/// written to the sink by then.
+///
+/// ```rust
+/// use unicode_normalization::{Form, Normalizer};
+///
+/// let mut normalizer = Normalizer::new(Form::Nfc);
+/// let mut out = String::new();
+/// normalizer.push("Introduccio", &mut out).unwrap();
+/// assert_eq!(out, "Introducci");
+/// normalizer.push_char('\u{301}', &mut out).unwrap();
+/// normalizer.push("n", &mut out).unwrap();
+/// normalizer.finish(&mut out).unwrap();
+/// assert_eq!(out, "Introducci\u{f3}n");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Normalizer {
+    form: Form,
+    pending: String,
+}
+
+impl Normalizer {
+    /// Creates a normalizer into `form`.
+    pub fn new(form: Form) -> Normalizer {
+        Normalizer {
+            form,
+            pending: String::new(),
+        }
+    }
+
+    /// Returns the form this normalizer normalizes into.
+    pub fn form(&self) -> Form {
+        self.form
+    }
+
+    /// Push a piece of text, writing everything that's ready to `sink`.
+    pub fn push<W: fmt::Write>(&mut self, s: &str, sink: &mut W) -> fmt::Result {
+        let form = self.form;
+        let boundary = s.char_indices()
+            .rev()
+            .find(|&(_, c)| form.has_boundary_before(c))
+            .map(|(i, _)| i);
+        let i = match boundary {
+            Some(i) => i,
+            None => {
+                self.pending.push_str(s);
+                return Ok(());
+            }
+        };
+
+        if self.pending.is_empty() {
+            write_normalized(&s[..i], form, sink)?;
+        } else {
+            let len = self.pending.len();
+            self.pending.push_str(&s[..i]);
+            if let Err(e) = write_normalized(&self.pending, form, sink) {
+                self.pending.truncate(len);
+                return Err(e);
+            }
+            self.pending.clear();
+        }
+        self.pending.push_str(&s[i..]);
+        Ok(())
+    }
+
+    /// Push a single character, writing everything that's ready to `sink`.
+    pub fn push_char<W: fmt::Write>(&mut self, c: char, sink: &mut W) -> fmt::Result {
+        let mut buf = [0; 4];
+        self.push(c.encode_utf8(&mut buf), sink)
+    }
+
+    /// Write out all text that's still held back to `sink`.
+    ///
+    /// Text pushed afterwards is normalized independently of what came
+    /// before.
+    pub fn finish<W: fmt::Write>(&mut self, sink: &mut W) -> fmt::Result {
+        write_normalized(&self.pending, self.form, sink)?;
+        self.pending.clear();
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Normalization of large strings on several threads.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use rayon::prelude::*;
+use form::Form;
+use string::normalize_into;
+
+/// The length in bytes of the pieces a string is split into, give or take
+/// the distance to the next normalization boundary.
+const CHUNK_LEN: usize = 64 * 1024;
+
+/// Normalize `s` into `form`, splitting it into pieces that are normalized
+/// concurrently on rayon's thread pool.
+///
+/// The pieces are split at normalization boundaries, so the result is the
+/// same as normalizing `s` in one go. Strings shorter than a few tens of
+/// kilobytes are normalized on the current thread.
+///
+/// Only available with the `rayon` feature enabled.
+///
+/// ```rust
+/// use unicode_normalization::{par_normalize, Form};
+///
+/// let s = "Introduccio\u{301}n ".repeat(10000);
+/// assert_eq!(par_normalize(&s, Form::Nfc), "Introducci\u{f3}n ".repeat(10000));
+/// ```
+pub fn par_normalize(s: &str, form: Form) -> String {
+    normalize_chunked(s, form, CHUNK_LEN)
+}
+
+pub(crate) fn normalize_chunked(s: &str, form: Form, chunk_len: usize) -> String {
+    let pieces = split(s, form, chunk_len);
+    if pieces.len() <= 1 {
+        let mut out = String::with_capacity(s.len());
+        normalize_into(s, form, &mut out);
+        return out;
+    }
+    let normalized: Vec<String> = pieces.par_iter()
+        .map(|piece| {
+            let mut out = String::with_capacity(piece.len());
+            normalize_into(piece, form, &mut out);
+            out
+        })
+        .collect();
+    normalized.concat()
+}
+
+/// Split `s` into pieces of about `chunk_len` bytes, each ending at a
+/// normalization boundary.
+pub(crate) fn split(s: &str, form: Form, chunk_len: usize) -> Vec<&str> {
+    let mut pieces = Vec::with_capacity(s.len() / chunk_len + 1);
+    let mut rest = s;
+    while rest.len() > chunk_len {
+        let end = form.next_boundary(rest, chunk_len);
+        let (piece, tail) = rest.split_at(end);
+        pieces.push(piece);
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        pieces.push(rest);
+    }
+    pieces
+}
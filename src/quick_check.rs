@@ -1,7 +1,15 @@
+#[cfg(feature = "alloc")]
 use UnicodeNormalization;
-use lookups::canonical_combining_class;
+use bulk::PlainRuns;
+use form::Form;
+use lookups::{
+    canonical_combining_class,
+    canonical_fully_decomposed,
+    changes_when_nfkc_casefolded,
+    changes_when_nfkc_simple_casefolded,
+};
 use stream_safe;
-use tables;
+use lookups;
 
 /// The QuickCheck algorithm can quickly determine if a text is or isn't
 /// normalized without any allocations in many cases, but it has to be able to
@@ -16,15 +24,78 @@ pub enum IsNormalized {
     Maybe,
 }
 
+/// Why a string isn't definitely normalized, as found by the `check_*_quick`
+/// functions.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum QuickCheckReason {
+    /// The quick check property of the character is `No` for the form.
+    QcNo,
+    /// The quick check property of the character is `Maybe` for the form, so
+    /// it might compose with the character before it.
+    QcMaybe,
+    /// The character has a lower nonzero canonical combining class than the
+    /// character before it, so the text isn't in canonical order.
+    CanonicalOrder,
+    /// The character makes a run of more than 30 nonstarters, so the text
+    /// isn't stream-safe.
+    StreamSafe,
+}
+
+/// The first character that keeps a string from being definitely normalized.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct QuickCheckIssue {
+    /// The byte offset of the character in the string.
+    pub offset: usize,
+    /// The character itself.
+    pub ch: char,
+    /// What's wrong with the character.
+    pub reason: QuickCheckReason,
+}
+
+impl QuickCheckIssue {
+    /// The result of the plain quick check for the string: `Maybe` for
+    /// `QuickCheckReason::QcMaybe`, and `No` otherwise.
+    #[inline]
+    pub fn is_normalized(&self) -> IsNormalized {
+        match self.reason {
+            QuickCheckReason::QcMaybe => IsNormalized::Maybe,
+            _ => IsNormalized::No,
+        }
+    }
+}
+
 // https://unicode.org/reports/tr15/#Detecting_Normalization_Forms
 #[inline]
-fn quick_check<F, I>(s: I, is_allowed: F, stream_safe: bool) -> IsNormalized
+pub(crate) fn quick_check<F, I>(s: I, is_allowed: F, stream_safe: bool) -> IsNormalized
     where I: Iterator<Item=char>, F: Fn(char) -> IsNormalized
+{
+    match first_issue(s.map(|ch| (0, ch)), is_allowed, stream_safe) {
+        Ok(()) => IsNormalized::Yes,
+        Err(issue) => issue.is_normalized(),
+    }
+}
+
+/// Run the quick check over a string, skipping over runs of characters below
+/// U+0300 in bulk.
+#[cfg(feature = "alloc")]
+#[inline]
+fn quick_check_str(s: &str, form: Form, stream_safe: bool) -> IsNormalized {
+    match first_issue(PlainRuns::new(s, form), form.qc(), stream_safe) {
+        Ok(()) => IsNormalized::Yes,
+        Err(issue) => issue.is_normalized(),
+    }
+}
+
+/// Run the quick check over `iter`, returning the first character that makes
+/// it fail, or the first one that makes it return `Maybe` if none does.
+#[inline]
+fn first_issue<F, I>(iter: I, is_allowed: F, stream_safe: bool) -> Result<(), QuickCheckIssue>
+    where I: Iterator<Item=(usize, char)>, F: Fn(char) -> IsNormalized
 {
     let mut last_cc = 0u8;
     let mut nonstarter_count = 0;
-    let mut result = IsNormalized::Yes;
-    for ch in s {
+    let mut maybe = None;
+    for (offset, ch) in iter {
         // For ASCII we know it's always allowed and a starter
         if ch <= '\x7f' {
             last_cc = 0;
@@ -32,16 +103,20 @@ fn quick_check<F, I>(s: I, is_allowed: F, stream_safe: bool) -> IsNormalized
             continue;
         }
 
+        let issue = |reason| QuickCheckIssue { offset, ch, reason };
+
         // Otherwise, lookup the combining class and QC property
         let cc = canonical_combining_class(ch);
         if last_cc > cc && cc != 0 {
-            return IsNormalized::No;
+            return Err(issue(QuickCheckReason::CanonicalOrder));
         }
         match is_allowed(ch) {
             IsNormalized::Yes => (),
-            IsNormalized::No => return IsNormalized::No,
+            IsNormalized::No => return Err(issue(QuickCheckReason::QcNo)),
             IsNormalized::Maybe => {
-                result = IsNormalized::Maybe;
+                if maybe.is_none() {
+                    maybe = Some(issue(QuickCheckReason::QcMaybe));
+                }
             },
         }
         if stream_safe {
@@ -50,7 +125,7 @@ fn quick_check<F, I>(s: I, is_allowed: F, stream_safe: bool) -> IsNormalized
             // If we're above `MAX_NONSTARTERS`, we're definitely *not*
             // stream-safe normalized.
             if nonstarter_count + decomp.leading_nonstarters > stream_safe::MAX_NONSTARTERS {
-                return IsNormalized::No;
+                return Err(issue(QuickCheckReason::StreamSafe));
             }
             if decomp.leading_nonstarters == decomp.decomposition_len {
                 nonstarter_count += decomp.decomposition_len;
@@ -60,7 +135,42 @@ fn quick_check<F, I>(s: I, is_allowed: F, stream_safe: bool) -> IsNormalized
         }
         last_cc = cc;
     }
-    result
+    match maybe {
+        Some(issue) => Err(issue),
+        None => Ok(()),
+    }
+}
+
+/// Find the end of the longest prefix of the text that's definitely normalized
+/// and that ends right before a starter, so the rest of the text can be
+/// normalized independently. Returns `None` if all of `iter` is normalized.
+#[cfg(feature = "alloc")]
+#[inline]
+pub(crate) fn normalized_prefix_len<I, F>(iter: I, is_allowed: F) -> Option<usize>
+    where I: Iterator<Item=(usize, char)>, F: Fn(char) -> IsNormalized
+{
+    let mut last_cc = 0u8;
+    let mut last_starter = 0;
+    for (i, ch) in iter {
+        if ch <= '\x7f' {
+            last_cc = 0;
+            last_starter = i;
+            continue;
+        }
+
+        let cc = canonical_combining_class(ch);
+        if last_cc > cc && cc != 0 {
+            return Some(last_starter);
+        }
+        if is_allowed(ch) != IsNormalized::Yes {
+            return Some(last_starter);
+        }
+        if cc == 0 {
+            last_starter = i;
+        }
+        last_cc = cc;
+    }
+    None
 }
 
 /// Quickly check if a string is in NFC, potentially returning
@@ -68,64 +178,215 @@ fn quick_check<F, I>(s: I, is_allowed: F, stream_safe: bool) -> IsNormalized
 /// like `s.chars().nfc().eq(s.chars())` should suffice.
 #[inline]
 pub fn is_nfc_quick<I: Iterator<Item=char>>(s: I) -> IsNormalized {
-    quick_check(s, tables::qc_nfc, false)
+    quick_check(s, lookups::qc_nfc, false)
 }
 
 
 /// Quickly check if a string is in NFKC.
 #[inline]
 pub fn is_nfkc_quick<I: Iterator<Item=char>>(s: I) -> IsNormalized {
-    quick_check(s, tables::qc_nfkc, false)
+    quick_check(s, lookups::qc_nfkc, false)
+}
+
+/// Quick check a string for being unchanged by the case folding in `casefold`,
+/// on top of NFKC.
+#[inline]
+fn quick_check_casefolded<I, F>(s: I, changes: F) -> IsNormalized
+    where I: Iterator<Item=char>, F: Fn(char) -> bool
+{
+    // `quick_check` takes all of ASCII to be fine, so uppercase ASCII has to
+    // be caught separately.
+    let mut ascii_uppercase = false;
+    let result = quick_check(s.inspect(|c| ascii_uppercase |= c.is_ascii_uppercase()),
+        |c| if changes(c) { IsNormalized::No } else { lookups::qc_nfkc(c) },
+        false);
+    if ascii_uppercase {
+        IsNormalized::No
+    } else {
+        result
+    }
+}
+
+/// Quickly check if a string is unchanged by `toNFKC_Casefold`, potentially
+/// returning `IsNormalized::Maybe` if further checks are necessary.
+///
+/// This uses the `Changes_When_NFKC_Casefolded` property.
+#[inline]
+pub fn is_nfkc_casefolded_quick<I: Iterator<Item=char>>(s: I) -> IsNormalized {
+    quick_check_casefolded(s, changes_when_nfkc_casefolded)
+}
+
+/// Quickly check if a string is unchanged by the NFKC_SimpleCasefold variant
+/// of `toNFKC_Casefold`, potentially returning `IsNormalized::Maybe` if
+/// further checks are necessary.
+#[inline]
+pub fn is_nfkc_simple_casefolded_quick<I: Iterator<Item=char>>(s: I) -> IsNormalized {
+    quick_check_casefolded(s, changes_when_nfkc_simple_casefolded)
 }
 
 /// Quickly check if a string is in NFD.
 #[inline]
 pub fn is_nfd_quick<I: Iterator<Item=char>>(s: I) -> IsNormalized {
-    quick_check(s, tables::qc_nfd, false)
+    quick_check(s, lookups::qc_nfd, false)
 }
 
 /// Quickly check if a string is in NFKD.
 #[inline]
 pub fn is_nfkd_quick<I: Iterator<Item=char>>(s: I) -> IsNormalized {
-    quick_check(s, tables::qc_nfkd, false)
+    quick_check(s, lookups::qc_nfkd, false)
 }
 
 /// Quickly check if a string is Stream-Safe NFC.
 #[inline]
 pub fn is_nfc_stream_safe_quick<I: Iterator<Item=char>>(s: I) -> IsNormalized {
-    quick_check(s, tables::qc_nfc, true)
+    quick_check(s, lookups::qc_nfc, true)
 }
 
 /// Quickly check if a string is Stream-Safe NFD.
 #[inline]
 pub fn is_nfd_stream_safe_quick<I: Iterator<Item=char>>(s: I) -> IsNormalized {
-    quick_check(s, tables::qc_nfd, true)
+    quick_check(s, lookups::qc_nfd, true)
+}
+
+/// Quickly check if a string is in NFC, returning where and why the check
+/// fails or can't tell.
+///
+/// Like `is_nfc_quick`, a `QuickCheckReason::QcMaybe` issue is only reported
+/// when no character makes the check fail outright.
+///
+/// ```rust
+/// use unicode_normalization::{QuickCheckReason, check_nfc_quick};
+///
+/// assert_eq!(check_nfc_quick("Introducci\u{f3}n"), Ok(()));
+/// let issue = check_nfc_quick("Introduccio\u{301}n").unwrap_err();
+/// assert_eq!((issue.offset, issue.ch, issue.reason), (11, '\u{301}', QuickCheckReason::QcMaybe));
+/// ```
+#[inline]
+pub fn check_nfc_quick(s: &str) -> Result<(), QuickCheckIssue> {
+    first_issue(PlainRuns::new(s, Form::Nfc), lookups::qc_nfc, false)
+}
+
+/// Quickly check if a string is in NFKC, returning where and why the check
+/// fails or can't tell.
+#[inline]
+pub fn check_nfkc_quick(s: &str) -> Result<(), QuickCheckIssue> {
+    first_issue(PlainRuns::new(s, Form::Nfkc), lookups::qc_nfkc, false)
+}
+
+/// Quickly check if a string is in NFD, returning where and why the check
+/// fails.
+#[inline]
+pub fn check_nfd_quick(s: &str) -> Result<(), QuickCheckIssue> {
+    first_issue(PlainRuns::new(s, Form::Nfd), lookups::qc_nfd, false)
+}
+
+/// Quickly check if a string is in NFKD, returning where and why the check
+/// fails.
+#[inline]
+pub fn check_nfkd_quick(s: &str) -> Result<(), QuickCheckIssue> {
+    first_issue(PlainRuns::new(s, Form::Nfkd), lookups::qc_nfkd, false)
+}
+
+/// Quickly check if a string is Stream-Safe NFC, returning where and why the
+/// check fails or can't tell.
+#[inline]
+pub fn check_nfc_stream_safe_quick(s: &str) -> Result<(), QuickCheckIssue> {
+    first_issue(PlainRuns::new(s, Form::Nfc), lookups::qc_nfc, true)
+}
+
+/// Quickly check if a string is Stream-Safe NFD, returning where and why the
+/// check fails.
+#[inline]
+pub fn check_nfd_stream_safe_quick(s: &str) -> Result<(), QuickCheckIssue> {
+    first_issue(PlainRuns::new(s, Form::Nfd), lookups::qc_nfd, true)
+}
+
+/// Quickly check if a string is in FCD ("Fast C or D") form, as described in
+/// [Unicode Technical Note #5](http://www.unicode.org/notes/tn5/#FCD).
+///
+/// Text is in FCD if decomposing each character separately already puts it
+/// in canonical order. Unlike the other quick checks this never returns
+/// `IsNormalized::Maybe`.
+#[inline]
+pub fn is_fcd_quick<I: Iterator<Item=char>>(s: I) -> IsNormalized {
+    let mut last_trail_cc = 0;
+    for ch in s {
+        if ch <= '\x7f' {
+            last_trail_cc = 0;
+            continue;
+        }
+        let (lead_cc, trail_cc) = match canonical_fully_decomposed(ch) {
+            Some(decomp) => {
+                (canonical_combining_class(decomp[0]),
+                 canonical_combining_class(decomp[decomp.len() - 1]))
+            }
+            None => {
+                let cc = canonical_combining_class(ch);
+                (cc, cc)
+            }
+        };
+        if lead_cc != 0 && lead_cc < last_trail_cc {
+            return IsNormalized::No;
+        }
+        last_trail_cc = trail_cc;
+    }
+    IsNormalized::Yes
+}
+
+/// Authoritatively check if a string is in FCD.
+#[inline]
+pub fn is_fcd(s: &str) -> bool {
+    is_fcd_quick(s.chars()) == IsNormalized::Yes
+}
+
+/// Check a string the quick check returned `Maybe` for, by normalizing only
+/// the segments around the characters it couldn't decide on.
+#[cfg(feature = "alloc")]
+fn maybe_segments_normalized(s: &str, form: Form) -> bool {
+    let is_allowed = form.qc();
+    // The end of the last segment checked.
+    let mut checked = 0;
+    for (i, ch) in s.char_indices() {
+        if i < checked || ch <= '\x7f' || is_allowed(ch) != IsNormalized::Maybe {
+            continue;
+        }
+        let start = form.prev_boundary(s, i);
+        let end = form.next_boundary(s, i + ch.len_utf8());
+        if !form.is_normalized(s[start..end].chars()) {
+            return false;
+        }
+        checked = end;
+    }
+    true
 }
 
 /// Authoritatively check if a string is in NFC.
+#[cfg(feature = "alloc")]
 #[inline]
 pub fn is_nfc(s: &str) -> bool {
-    match is_nfc_quick(s.chars()) {
+    match quick_check_str(s, Form::Nfc, false) {
         IsNormalized::Yes => true,
         IsNormalized::No => false,
-        IsNormalized::Maybe => s.chars().eq(s.chars().nfc()),
+        IsNormalized::Maybe => maybe_segments_normalized(s, Form::Nfc),
     }
 }
 
 /// Authoritatively check if a string is in NFKC.
+#[cfg(feature = "alloc")]
 #[inline]
 pub fn is_nfkc(s: &str) -> bool {
-    match is_nfkc_quick(s.chars()) {
+    match quick_check_str(s, Form::Nfkc, false) {
         IsNormalized::Yes => true,
         IsNormalized::No => false,
-        IsNormalized::Maybe => s.chars().eq(s.chars().nfkc()),
+        IsNormalized::Maybe => maybe_segments_normalized(s, Form::Nfkc),
     }
 }
 
 /// Authoritatively check if a string is in NFD.
+#[cfg(feature = "alloc")]
 #[inline]
 pub fn is_nfd(s: &str) -> bool {
-    match is_nfd_quick(s.chars()) {
+    match quick_check_str(s, Form::Nfd, false) {
         IsNormalized::Yes => true,
         IsNormalized::No => false,
         IsNormalized::Maybe => s.chars().eq(s.chars().nfd()),
@@ -133,19 +394,44 @@ pub fn is_nfd(s: &str) -> bool {
 }
 
 /// Authoritatively check if a string is in NFKD.
+#[cfg(feature = "alloc")]
 #[inline]
 pub fn is_nfkd(s: &str) -> bool {
-    match is_nfkd_quick(s.chars()) {
+    match quick_check_str(s, Form::Nfkd, false) {
         IsNormalized::Yes => true,
         IsNormalized::No => false,
         IsNormalized::Maybe => s.chars().eq(s.chars().nfkd()),
     }
 }
 
+/// Authoritatively check if a string is unchanged by `toNFKC_Casefold`.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn is_nfkc_casefolded(s: &str) -> bool {
+    match is_nfkc_casefolded_quick(s.chars()) {
+        IsNormalized::Yes => true,
+        IsNormalized::No => false,
+        IsNormalized::Maybe => s.chars().eq(s.chars().nfkc_casefold()),
+    }
+}
+
+/// Authoritatively check if a string is unchanged by the NFKC_SimpleCasefold
+/// variant of `toNFKC_Casefold`.
+#[cfg(feature = "alloc")]
+#[inline]
+pub fn is_nfkc_simple_casefolded(s: &str) -> bool {
+    match is_nfkc_simple_casefolded_quick(s.chars()) {
+        IsNormalized::Yes => true,
+        IsNormalized::No => false,
+        IsNormalized::Maybe => s.chars().eq(s.chars().nfkc_simple_casefold()),
+    }
+}
+
 /// Authoritatively check if a string is Stream-Safe NFC.
+#[cfg(feature = "alloc")]
 #[inline]
 pub fn is_nfc_stream_safe(s: &str) -> bool {
-    match is_nfc_stream_safe_quick(s.chars()) {
+    match quick_check_str(s, Form::Nfc, true) {
         IsNormalized::Yes => true,
         IsNormalized::No => false,
         IsNormalized::Maybe => s.chars().eq(s.chars().stream_safe().nfc()),
@@ -153,9 +439,10 @@ pub fn is_nfc_stream_safe(s: &str) -> bool {
 }
 
 /// Authoritatively check if a string is Stream-Safe NFD.
+#[cfg(feature = "alloc")]
 #[inline]
 pub fn is_nfd_stream_safe(s: &str) -> bool {
-    match is_nfd_stream_safe_quick(s.chars()) {
+    match quick_check_str(s, Form::Nfd, true) {
         IsNormalized::Yes => true,
         IsNormalized::No => false,
         IsNormalized::Maybe => s.chars().eq(s.chars().stream_safe().nfd()),
@@ -166,9 +453,23 @@ pub fn is_nfd_stream_safe(s: &str) -> bool {
 mod tests {
     use super::{
         IsNormalized,
+        QuickCheckIssue,
+        QuickCheckReason,
+        check_nfc_quick,
+        check_nfc_stream_safe_quick,
+        check_nfd_quick,
+        check_nfd_stream_safe_quick,
+        check_nfkc_quick,
+        check_nfkd_quick,
+        is_nfc_quick,
         is_nfc_stream_safe_quick,
+        is_nfd_quick,
         is_nfd_stream_safe_quick,
+        is_nfkc_quick,
+        is_nfkd_quick,
     };
+    use normalization_tests::NORMALIZATION_TESTS;
+    use std::string::String;
 
     #[test]
     fn test_stream_safe_nfd() {
@@ -187,4 +488,42 @@ mod tests {
         let too_much = "not ok\u{e0}\u{031b}\u{0316}\u{0317}\u{0318}\u{0319}\u{031c}\u{031d}\u{031e}\u{0301}\u{0302}\u{0303}\u{0304}\u{0305}\u{0306}\u{0307}\u{0308}\u{0309}\u{030a}\u{030b}\u{030c}\u{030d}\u{030e}\u{030f}\u{0310}\u{0311}\u{0312}\u{0313}\u{0314}\u{0315}\u{031a}y";
         assert_eq!(is_nfc_stream_safe_quick(too_much.chars()), IsNormalized::No);
     }
+
+    #[test]
+    fn test_check_quick() {
+        let issue = |offset, ch, reason| Err(QuickCheckIssue { offset, ch, reason });
+        assert_eq!(check_nfc_quick(""), Ok(()));
+        assert_eq!(check_nfc_quick("a\u{301}"), issue(1, '\u{301}', QuickCheckReason::QcMaybe));
+        assert_eq!(check_nfc_quick("a\u{301}\u{212b}"), issue(3, '\u{212b}', QuickCheckReason::QcNo));
+        assert_eq!(check_nfd_quick("x\u{e9}"), issue(1, '\u{e9}', QuickCheckReason::QcNo));
+        assert_eq!(check_nfd_quick("a\u{301}\u{323}"), issue(3, '\u{323}', QuickCheckReason::CanonicalOrder));
+        assert_eq!(check_nfkc_quick("\u{fb01}"), issue(0, '\u{fb01}', QuickCheckReason::QcNo));
+        assert_eq!(check_nfkd_quick("fi"), Ok(()));
+
+        let mut too_much = String::from("a");
+        for _ in 0..31 {
+            too_much.push('\u{301}');
+        }
+        assert_eq!(check_nfd_stream_safe_quick(&too_much), issue(61, '\u{301}', QuickCheckReason::StreamSafe));
+        assert_eq!(check_nfc_stream_safe_quick(&too_much).unwrap_err().reason, QuickCheckReason::StreamSafe);
+        assert_eq!(check_nfd_quick(&too_much), Ok(()));
+    }
+
+    #[test]
+    fn test_check_quick_agrees() {
+        let result = |r: Result<(), QuickCheckIssue>| r.err().map_or(IsNormalized::Yes, |i| i.is_normalized());
+        for test in NORMALIZATION_TESTS {
+            for &s in &[test.source, test.nfc, test.nfd, test.nfkc, test.nfkd] {
+                assert_eq!(result(check_nfc_quick(s)), is_nfc_quick(s.chars()));
+                assert_eq!(result(check_nfd_quick(s)), is_nfd_quick(s.chars()));
+                assert_eq!(result(check_nfkc_quick(s)), is_nfkc_quick(s.chars()));
+                assert_eq!(result(check_nfkd_quick(s)), is_nfkd_quick(s.chars()));
+                assert_eq!(result(check_nfc_stream_safe_quick(s)), is_nfc_stream_safe_quick(s.chars()));
+                assert_eq!(result(check_nfd_stream_safe_quick(s)), is_nfd_stream_safe_quick(s.chars()));
+                if let Err(issue) = check_nfc_quick(s) {
+                    assert_eq!(s[issue.offset..].chars().next(), Some(issue.ch));
+                }
+            }
+        }
+    }
 }
@@ -9,8 +9,19 @@
 // except according to those terms.
 
 use decompose::Decompositions;
+use normalize::{Composed, Composer};
+#[cfg(feature = "alloc")]
 use smallvec::SmallVec;
-use std::fmt::{self, Write};
+#[cfg(not(feature = "alloc"))]
+use array_vec::ArrayVec;
+use core::fmt::{self, Write};
+
+#[cfg(feature = "alloc")]
+type Buffer = SmallVec<[char; 4]>;
+// Only nonstarters are ever held back, and without an allocator the
+// decomposition doesn't go past runs of `MAX_BUFFERED_NONSTARTERS`.
+#[cfg(not(feature = "alloc"))]
+type Buffer = ArrayVec<[char; 32]>;
 
 #[derive(Clone)]
 enum RecompositionState {
@@ -24,9 +35,10 @@ enum RecompositionState {
 pub struct Recompositions<I> {
     iter: Decompositions<I>,
     state: RecompositionState,
-    buffer: SmallVec<[char; 4]>,
-    composee: Option<char>,
-    last_ccc: Option<u8>,
+    // Nonstarters left after the composer's starter, waiting for it to go
+    // out first.
+    buffer: Buffer,
+    composer: Composer,
 }
 
 #[inline]
@@ -34,9 +46,8 @@ pub fn new_canonical<I: Iterator<Item=char>>(iter: I) -> Recompositions<I> {
     Recompositions {
         iter: super::decompose::new_canonical(iter),
         state: self::RecompositionState::Composing,
-        buffer: SmallVec::new(),
-        composee: None,
-        last_ccc: None,
+        buffer: Buffer::new(),
+        composer: Composer::new(false),
     }
 }
 
@@ -45,9 +56,18 @@ pub fn new_compatible<I: Iterator<Item=char>>(iter: I) -> Recompositions<I> {
     Recompositions {
         iter: super::decompose::new_compatible(iter),
         state: self::RecompositionState::Composing,
-        buffer: SmallVec::new(),
-        composee: None,
-        last_ccc: None,
+        buffer: Buffer::new(),
+        composer: Composer::new(false),
+    }
+}
+
+#[inline]
+pub fn new_contiguous<I: Iterator<Item=char>>(iter: I) -> Recompositions<I> {
+    Recompositions {
+        iter: super::decompose::new_canonical(iter),
+        state: self::RecompositionState::Composing,
+        buffer: Buffer::new(),
+        composer: Composer::new(true),
     }
 }
 
@@ -62,63 +82,30 @@ impl<I: Iterator<Item=char>> Iterator for Recompositions<I> {
             match self.state {
                 Composing => {
                     for ch in self.iter.by_ref() {
-                        let ch_class = super::char::canonical_combining_class(ch);
-                        let k = match self.composee {
-                            None => {
-                                if ch_class != 0 {
-                                    return Some(ch);
+                        let k = self.composer.starter();
+                        match self.composer.push(ch) {
+                            Composed::Into(_) => {}
+                            Composed::Starter => {
+                                // The previous starter and the nonstarters
+                                // left after it are done.
+                                if !self.buffer.is_empty() {
+                                    self.state = Purging(0);
                                 }
-                                self.composee = Some(ch);
-                                continue;
-                            },
-                            Some(k) => k,
-                        };
-                        match self.last_ccc {
-                            None => {
-                                match super::char::compose(k, ch) {
-                                    Some(r) => {
-                                        self.composee = Some(r);
-                                        continue;
-                                    }
-                                    None => {
-                                        if ch_class == 0 {
-                                            self.composee = Some(ch);
-                                            return Some(k);
-                                        }
-                                        self.buffer.push(ch);
-                                        self.last_ccc = Some(ch_class);
-                                    }
+                                if k.is_some() {
+                                    return k;
                                 }
                             }
-                            Some(l_class) => {
-                                if l_class >= ch_class {
-                                    // `ch` is blocked from `composee`
-                                    if ch_class == 0 {
-                                        self.composee = Some(ch);
-                                        self.last_ccc = None;
-                                        self.state = Purging(0);
-                                        return Some(k);
-                                    }
-                                    self.buffer.push(ch);
-                                    self.last_ccc = Some(ch_class);
-                                    continue;
-                                }
-                                match super::char::compose(k, ch) {
-                                    Some(r) => {
-                                        self.composee = Some(r);
-                                        continue;
-                                    }
-                                    None => {
-                                        self.buffer.push(ch);
-                                        self.last_ccc = Some(ch_class);
-                                    }
+                            Composed::Nonstarter => {
+                                if k.is_none() {
+                                    return Some(ch);
                                 }
+                                self.buffer.push(ch);
                             }
                         }
                     }
                     self.state = Finished(0);
-                    if self.composee.is_some() {
-                        return self.composee.take();
+                    if let Some(k) = self.composer.take_starter() {
+                        return Some(k);
                     }
                 }
                 Purging(next) => {
@@ -137,7 +124,7 @@ impl<I: Iterator<Item=char>> Iterator for Recompositions<I> {
                     match self.buffer.get(next).cloned() {
                         None => {
                             self.buffer.clear();
-                            return self.composee.take()
+                            return self.composer.take_starter()
                         }
                         s => {
                             self.state = Finished(next + 1);
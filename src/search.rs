@@ -0,0 +1,251 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Substring search under canonical or compatibility equivalence.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::cmp;
+use core::ops::Range;
+use form::Form;
+use indices::NormalizedIndices;
+
+/// A searcher for the occurrences of a needle in strings, up to canonical or
+/// compatibility equivalence.
+///
+/// Both the needle and the searched string are decomposed, and the needle is
+/// looked for in the decomposition. Matches are byte ranges in the searched
+/// string, covering all characters that contributed to them.
+///
+/// By default a match has to start and end at a normalization boundary, so
+/// the needle "e" doesn't match the "e" in "\u{e9}", whether precomposed or
+/// not. `partial` allows such matches. An empty needle never matches.
+///
+/// ```rust
+/// use unicode_normalization::CanonicalSearcher;
+///
+/// let searcher = CanonicalSearcher::new("\u{e9}t\u{e9}");
+/// let text = "L'e\u{301}te\u{301}, l'\u{e9}t\u{e9} et l'\u{e9}t\u{e9}\u{301}";
+/// let matches: Vec<_> = searcher.find_iter(text).collect();
+/// assert_eq!(matches, [2..9, 13..18]);
+///
+/// let searcher = CanonicalSearcher::new("e").partial(true);
+/// assert_eq!(searcher.find("\u{e9}"), Some(0..2));
+/// ```
+#[derive(Clone, Debug)]
+pub struct CanonicalSearcher {
+    // The decomposed needle.
+    needle: Vec<char>,
+    // For each prefix of the needle, the length of its longest proper prefix
+    // that's also a suffix of it.
+    fail: Vec<usize>,
+    form: Form,
+    partial: bool,
+}
+
+impl CanonicalSearcher {
+    /// Creates a searcher for `needle` up to canonical equivalence.
+    pub fn new(needle: &str) -> CanonicalSearcher {
+        CanonicalSearcher::with_form(needle, Form::Nfd)
+    }
+
+    /// Creates a searcher for `needle` up to compatibility equivalence, so
+    /// for example "fi" also matches "\u{fb01}".
+    pub fn new_compatible(needle: &str) -> CanonicalSearcher {
+        CanonicalSearcher::with_form(needle, Form::Nfkd)
+    }
+
+    fn with_form(needle: &str, form: Form) -> CanonicalSearcher {
+        let mut searcher = CanonicalSearcher {
+            needle: Vec::new(),
+            fail: Vec::new(),
+            form,
+            partial: false,
+        };
+        form.extend(needle.chars(), &mut searcher.needle);
+        searcher.build_fail();
+        searcher
+    }
+
+    fn build_fail(&mut self) {
+        let needle = &self.needle;
+        let mut fail = Vec::with_capacity(needle.len());
+        let mut k = 0;
+        for (i, &c) in needle.iter().enumerate() {
+            while k > 0 && needle[k] != c {
+                k = fail[k - 1];
+            }
+            if i > 0 && needle[k] == c {
+                k += 1;
+            }
+            fail.push(k);
+        }
+        self.fail = fail;
+    }
+
+    /// Sets whether to also find matches that don't start or end at a
+    /// normalization boundary, like "e" in "\u{e9}".
+    pub fn partial(mut self, partial: bool) -> CanonicalSearcher {
+        self.partial = partial;
+        self
+    }
+
+    /// Returns the first match in `haystack`, if any.
+    pub fn find(&self, haystack: &str) -> Option<Range<usize>> {
+        self.find_iter(haystack).next()
+    }
+
+    /// Returns an iterator over the non-overlapping matches in `haystack`.
+    pub fn find_iter<'a, 'b>(&'a self, haystack: &'b str) -> CanonicalMatches<'a, 'b> {
+        CanonicalMatches {
+            searcher: self,
+            iter: NormalizedIndices::new(haystack, self.form),
+            window: VecDeque::with_capacity(self.needle.len()),
+            matched: 0,
+            candidate: None,
+        }
+    }
+
+    /// Whether normalization of the searched string never affects
+    /// characters on both sides of `c`, so a match may end before it.
+    #[inline]
+    fn is_boundary(&self, c: char) -> bool {
+        let composed = match self.form {
+            Form::Nfkd => Form::Nfkc,
+            _ => Form::Nfc,
+        };
+        self.partial || composed.has_boundary_before(c)
+    }
+}
+
+/// External iterator for the matches of a `CanonicalSearcher` in a string.
+#[derive(Clone)]
+pub struct CanonicalMatches<'a, 'b> {
+    searcher: &'a CanonicalSearcher,
+    iter: NormalizedIndices<'b>,
+    // The last decomposed characters of the string, with their ranges.
+    window: VecDeque<(char, Range<usize>)>,
+    // The length of the needle's prefix matching the end of the window.
+    matched: usize,
+    // A match still waiting for the next character to tell whether it ends
+    // at a boundary.
+    candidate: Option<Range<usize>>,
+}
+
+impl<'a, 'b> CanonicalMatches<'a, 'b> {
+    fn feed(&mut self, c: char, range: Range<usize>) {
+        let needle = &self.searcher.needle;
+        self.window.push_back((c, range));
+        if self.window.len() > needle.len() {
+            self.window.pop_front();
+        }
+        while self.matched > 0 && needle[self.matched] != c {
+            self.matched = self.searcher.fail[self.matched - 1];
+        }
+        if needle[self.matched] == c {
+            self.matched += 1;
+        }
+        if self.matched == needle.len() {
+            self.matched = self.searcher.fail[self.matched - 1];
+            if self.searcher.is_boundary(self.window[0].0) {
+                let start = self.window.iter().map(|item| item.1.start).fold(usize::max_value(), cmp::min);
+                let end = self.window.iter().map(|item| item.1.end).fold(0, cmp::max);
+                self.candidate = Some(start..end);
+            }
+        }
+    }
+}
+
+impl<'a, 'b> Iterator for CanonicalMatches<'a, 'b> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Range<usize>> {
+        if self.searcher.needle.is_empty() {
+            return None;
+        }
+        loop {
+            let next = self.iter.next();
+            if let Some(candidate) = self.candidate.take() {
+                let at_boundary = match next {
+                    Some((c, _)) => self.searcher.is_boundary(c),
+                    None => true,
+                };
+                if at_boundary {
+                    // Matches don't overlap, so start over after this one.
+                    self.matched = 0;
+                    self.window.clear();
+                    if let Some((c, range)) = next {
+                        self.feed(c, range);
+                    }
+                    return Some(candidate);
+                }
+            }
+            let (c, range) = next?;
+            self.feed(c, range);
+        }
+    }
+}
+
+/// Returns the byte range of the first occurrence of `needle` in `haystack`
+/// up to canonical equivalence, if any.
+///
+/// See `CanonicalSearcher` for the details, and to search repeatedly.
+///
+/// ```rust
+/// use unicode_normalization::find_canonical;
+///
+/// assert_eq!(find_canonical("Introduccio\u{301}n", "ci\u{f3}n"), Some(8..14));
+/// assert_eq!(find_canonical("Introducci\u{f3}n", "cio"), None);
+/// ```
+pub fn find_canonical(haystack: &str, needle: &str) -> Option<Range<usize>> {
+    CanonicalSearcher::new(needle).find(haystack)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CanonicalSearcher, find_canonical};
+    use std::vec::Vec;
+    use core::ops::Range;
+
+    fn find_all(searcher: &CanonicalSearcher, haystack: &str) -> Vec<Range<usize>> {
+        searcher.find_iter(haystack).collect()
+    }
+
+    #[test]
+    fn test_boundaries() {
+        assert_eq!(find_canonical("\u{e9}", "e"), None);
+        assert_eq!(find_canonical("e\u{301}", "e"), None);
+        assert_eq!(find_canonical("e\u{301}e", "e"), Some(3..4));
+        assert_eq!(find_canonical("xa\u{301}\u{323}y", "a\u{323}\u{301}"), Some(1..6));
+        assert_eq!(find_canonical("xa\u{323}\u{301}", "\u{1ea1}"), None);
+        assert_eq!(find_canonical("\u{ac01}", "\u{ac00}"), None);
+        assert_eq!(find_canonical("\u{ac00}\u{ac01}", "\u{ac00}"), Some(0..3));
+        assert_eq!(find_canonical("\u{1100}\u{1161}\u{ac01}", "\u{ac00}"), Some(0..6));
+
+        let partial = CanonicalSearcher::new("e").partial(true);
+        assert_eq!(partial.find("\u{e9}"), Some(0..2));
+        assert_eq!(partial.find("e\u{301}"), Some(0..1));
+        assert_eq!(find_all(&partial, "\u{e9}e\u{301}\u{e8}"), [0..2, 2..3, 5..7]);
+    }
+
+    #[test]
+    fn test_matches() {
+        assert_eq!(find_canonical("abc", ""), None);
+        assert_eq!(find_canonical("", "a"), None);
+        assert_eq!(find_all(&CanonicalSearcher::new("aa"), "aaaaa"), [0..2, 2..4]);
+        assert_eq!(find_all(&CanonicalSearcher::new("aba"), "abababa"), [0..3, 4..7]);
+        assert_eq!(find_all(&CanonicalSearcher::new("\u{c5}"), "A\u{30a}\u{212b}\u{c5}"), [0..3, 3..6, 6..8]);
+
+        assert_eq!(find_canonical("a\u{fb01}b", "fi"), None);
+        let compatible = CanonicalSearcher::new_compatible("fi");
+        assert_eq!(compatible.find("a\u{fb01}b"), Some(1..4));
+        assert_eq!(find_all(&compatible, "fi\u{fb01}"), [0..2, 2..5]);
+    }
+}
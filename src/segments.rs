@@ -0,0 +1,53 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use form::Form;
+
+/// External iterator for the maximal normalization segments of a string.
+///
+/// Created by `Form::segments`.
+#[derive(Clone, Debug)]
+pub struct Segments<'a> {
+    s: &'a str,
+    form: Form,
+}
+
+impl<'a> Segments<'a> {
+    #[inline]
+    pub(crate) fn new(s: &'a str, form: Form) -> Segments<'a> {
+        Segments { s, form }
+    }
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let mut chars = self.s.char_indices();
+        let mut prev = chars.next()?.1;
+        let mut end = self.s.len();
+        for (i, c) in chars {
+            if self.form.is_boundary_between(prev, c) {
+                end = i;
+                break;
+            }
+            prev = c;
+        }
+        let (segment, rest) = self.s.split_at(end);
+        self.s = rest;
+        Some(segment)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // A single segment may span all that's left.
+        (if self.s.is_empty() { 0 } else { 1 }, Some(self.s.len()))
+    }
+}
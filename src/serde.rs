@@ -0,0 +1,175 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Helpers for deserializing normalized strings with serde.
+//!
+//! The functions in this module are meant for serde's `deserialize_with`
+//! attribute. The `normalize_*` ones normalize incoming strings, while the
+//! `require_*` ones reject strings that aren't normalized already:
+//!
+//! ```rust,ignore
+//! #[derive(Deserialize)]
+//! struct User {
+//!     #[serde(deserialize_with = "unicode_normalization::serde::normalize_nfc")]
+//!     name: String,
+//!     #[serde(deserialize_with = "unicode_normalization::serde::require_nfc")]
+//!     login: String,
+//! }
+//! ```
+//!
+//! Only available with the `serde` feature enabled.
+
+use alloc::string::String;
+use core::fmt;
+use serde_crate::de::{Deserializer, Error, Unexpected, Visitor};
+use form::Form;
+use quick_check::{is_nfc, is_nfd, is_nfkc, is_nfkd};
+use string::{NormalizeInPlace, normalize_into};
+
+struct NormalizeVisitor(Form);
+
+impl<'de> Visitor<'de> for NormalizeVisitor {
+    type Value = String;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a string")
+    }
+
+    fn visit_str<E: Error>(self, s: &str) -> Result<String, E> {
+        let mut out = String::with_capacity(s.len());
+        normalize_into(s, self.0, &mut out);
+        Ok(out)
+    }
+
+    fn visit_string<E: Error>(self, mut s: String) -> Result<String, E> {
+        s.normalize_in_place(self.0);
+        Ok(s)
+    }
+}
+
+struct RequireVisitor(Form);
+
+impl RequireVisitor {
+    fn check<E: Error>(&self, s: &str) -> Result<(), E> {
+        let is_normalized = match self.0 {
+            Form::Nfc => is_nfc(s),
+            Form::Nfd => is_nfd(s),
+            Form::Nfkc => is_nfkc(s),
+            Form::Nfkd => is_nfkd(s),
+        };
+        if is_normalized {
+            Ok(())
+        } else {
+            Err(E::invalid_value(Unexpected::Str(s), self))
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for RequireVisitor {
+    type Value = String;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self.0 {
+            Form::Nfc => "a string in NFC",
+            Form::Nfd => "a string in NFD",
+            Form::Nfkc => "a string in NFKC",
+            Form::Nfkd => "a string in NFKD",
+        })
+    }
+
+    fn visit_str<E: Error>(self, s: &str) -> Result<String, E> {
+        self.check(s)?;
+        Ok(s.into())
+    }
+
+    fn visit_string<E: Error>(self, s: String) -> Result<String, E> {
+        self.check(&s)?;
+        Ok(s)
+    }
+}
+
+/// Deserialize a string and normalize it into NFC.
+pub fn normalize_nfc<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    deserializer.deserialize_string(NormalizeVisitor(Form::Nfc))
+}
+
+/// Deserialize a string and normalize it into NFD.
+pub fn normalize_nfd<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    deserializer.deserialize_string(NormalizeVisitor(Form::Nfd))
+}
+
+/// Deserialize a string and normalize it into NFKC.
+pub fn normalize_nfkc<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    deserializer.deserialize_string(NormalizeVisitor(Form::Nfkc))
+}
+
+/// Deserialize a string and normalize it into NFKD.
+pub fn normalize_nfkd<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    deserializer.deserialize_string(NormalizeVisitor(Form::Nfkd))
+}
+
+/// Deserialize a string, failing with an "invalid value" error if it's not
+/// in NFC.
+pub fn require_nfc<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    deserializer.deserialize_string(RequireVisitor(Form::Nfc))
+}
+
+/// Deserialize a string, failing with an "invalid value" error if it's not
+/// in NFD.
+pub fn require_nfd<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    deserializer.deserialize_string(RequireVisitor(Form::Nfd))
+}
+
+/// Deserialize a string, failing with an "invalid value" error if it's not
+/// in NFKC.
+pub fn require_nfkc<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    deserializer.deserialize_string(RequireVisitor(Form::Nfkc))
+}
+
+/// Deserialize a string, failing with an "invalid value" error if it's not
+/// in NFKD.
+pub fn require_nfkd<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    deserializer.deserialize_string(RequireVisitor(Form::Nfkd))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_nfc, normalize_nfkd, require_nfc, require_nfd};
+    use std::string::{String, ToString};
+    use serde_crate::de::IntoDeserializer;
+    use serde_crate::de::value::{Error, StrDeserializer, StringDeserializer};
+
+    fn from_str<'a>(s: &'a str) -> StrDeserializer<'a, Error> {
+        s.into_deserializer()
+    }
+
+    fn from_string(s: &str) -> StringDeserializer<Error> {
+        String::from(s).into_deserializer()
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize_nfc(from_str("Introduccio\u{301}n")).unwrap(), "Introducci\u{f3}n");
+        assert_eq!(normalize_nfc(from_string("Introduccio\u{301}n")).unwrap(), "Introducci\u{f3}n");
+        assert_eq!(normalize_nfkd(from_str("\u{fb01}\u{e9}")).unwrap(), "fie\u{301}");
+        assert_eq!(normalize_nfkd(from_string("\u{fb01}\u{e9}")).unwrap(), "fie\u{301}");
+    }
+
+    #[test]
+    fn test_require() {
+        assert_eq!(require_nfc(from_str("Introducci\u{f3}n")).unwrap(), "Introducci\u{f3}n");
+        assert_eq!(require_nfd(from_string("e\u{301}")).unwrap(), "e\u{301}");
+
+        let err = require_nfc(from_str("e\u{301}")).unwrap_err();
+        assert_eq!(err.to_string(), "invalid value: string \"e\\u{301}\", expected a string in NFC");
+        let err = require_nfd(from_string("\u{e9}")).unwrap_err();
+        assert_eq!(err.to_string(), "invalid value: string \"\u{e9}\", expected a string in NFD");
+    }
+}
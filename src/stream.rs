@@ -0,0 +1,144 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Normalization of asynchronous streams of text chunks.
+
+use alloc::string::String;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures_core::Stream;
+use form::Form;
+use normalizer::Normalizer;
+
+/// A stream normalizing the text chunks yielded by an inner stream.
+///
+/// Chunks are normalized as they arrive. Text from the last normalization
+/// boundary of a chunk onwards is carried over into the next one, since it
+/// might still reorder or compose with what follows, so the yielded chunks
+/// don't necessarily line up with the inner stream's. Chunks that would be
+/// empty aren't yielded at all.
+///
+/// The inner stream has to be `Unpin`; pin other streams with `Box::pin`
+/// first.
+///
+/// Only available with the `stream` feature enabled.
+///
+/// ```rust
+/// # extern crate futures_core;
+/// # extern crate unicode_normalization;
+/// # use std::pin::Pin;
+/// # use std::ptr;
+/// # use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+/// use futures_core::Stream;
+/// use unicode_normalization::{Form, NormalizedStream};
+///
+/// /// A stream of text frames, all of them ready.
+/// struct Frames(Vec<&'static str>);
+///
+/// impl Stream for Frames {
+///     type Item = &'static str;
+///
+///     fn poll_next(mut self: Pin<&mut Self>, _: &mut Context) -> Poll<Option<&'static str>> {
+///         Poll::Ready(if self.0.is_empty() { None } else { Some(self.0.remove(0)) })
+///     }
+/// }
+/// #
+/// # fn noop_raw_waker() -> RawWaker {
+/// #     fn clone(_: *const ()) -> RawWaker {
+/// #         noop_raw_waker()
+/// #     }
+/// #     fn noop(_: *const ()) {}
+/// #     static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+/// #     RawWaker::new(ptr::null(), &VTABLE)
+/// # }
+/// #
+/// # fn collect<S: Stream + Unpin>(mut stream: S) -> Vec<S::Item> {
+/// #     let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+/// #     let mut cx = Context::from_waker(&waker);
+/// #     let mut out = Vec::new();
+/// #     while let Poll::Ready(Some(item)) = Pin::new(&mut stream).poll_next(&mut cx) {
+/// #         out.push(item);
+/// #     }
+/// #     out
+/// # }
+///
+/// fn main() {
+///     let frames = Frames(vec!["Introduccio", "\u{301}n"]);
+///     // Polls the stream to its end, like `block_on(stream.collect())` with
+///     // the `futures` crate.
+///     let chunks: Vec<String> = collect(NormalizedStream::new(frames, Form::Nfc));
+///     assert_eq!(chunks, ["Introducci", "\u{f3}", "n"]);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct NormalizedStream<S> {
+    inner: S,
+    normalizer: Normalizer,
+    done: bool,
+}
+
+impl<S> NormalizedStream<S> {
+    /// Creates a stream normalizing the chunks of `inner` into `form`.
+    pub fn new(inner: S, form: Form) -> NormalizedStream<S> {
+        NormalizedStream {
+            inner,
+            normalizer: Normalizer::new(form),
+            done: false,
+        }
+    }
+
+    /// Gets a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Unwraps this stream, returning the underlying stream.
+    ///
+    /// Any text that's still held back is lost.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S> Stream for NormalizedStream<S>
+    where S: Stream + Unpin, S::Item: AsRef<str>
+{
+    type Item = String;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<String>> {
+        let this = &mut *self;
+        let mut out = String::new();
+        while !this.done {
+            match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some(chunk)) => {
+                    this.normalizer.push(chunk.as_ref(), &mut out).unwrap();
+                }
+                Poll::Ready(None) => {
+                    this.done = true;
+                    this.normalizer.finish(&mut out).unwrap();
+                }
+                Poll::Pending => break,
+            }
+            if !out.is_empty() {
+                return Poll::Ready(Some(out));
+            }
+        }
+        if this.done {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
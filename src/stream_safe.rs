@@ -2,10 +2,7 @@ use normalize::{
     hangul_decomposition_length,
     is_hangul_syllable,
 };
-use lookups::{
-    canonical_combining_class, canonical_fully_decomposed, compatibility_fully_decomposed,
-    stream_safe_trailing_nonstarters,
-};
+use lookups::properties;
 use tables::stream_safe_leading_nonstarters;
 
 pub(crate) const MAX_NONSTARTERS: usize = 30;
@@ -34,10 +31,7 @@ impl<I: Iterator<Item=char>> Iterator for StreamSafe<I> {
         if let Some(ch) = self.buffer.take() {
             return Some(ch);
         }
-        let next_ch = match self.iter.next() {
-            None => return None,
-            Some(c) => c,
-        };
+        let next_ch = self.iter.next()?;
         let d = classify_nonstarters(next_ch);
         if self.nonstarter_count + d.leading_nonstarters > MAX_NONSTARTERS {
             self.buffer = Some(next_ch);
@@ -82,18 +76,17 @@ pub(crate) fn classify_nonstarters(c: char) -> Decomposition {
             decomposition_len: hangul_decomposition_length(c),
         };
     }
-    let decomp = compatibility_fully_decomposed(c)
-        .or_else(|| canonical_fully_decomposed(c));
-    match decomp {
+    let props = properties(c);
+    match props.decomposition(true) {
         Some(decomp) => {
             Decomposition {
                 leading_nonstarters: stream_safe_leading_nonstarters(c),
-                trailing_nonstarters: stream_safe_trailing_nonstarters(c),
+                trailing_nonstarters: props.trailing_nonstarters(),
                 decomposition_len: decomp.len(),
             }
         },
         None => {
-            let is_nonstarter = canonical_combining_class(c) != 0;
+            let is_nonstarter = props.combining_class() != 0;
             let nonstarter = if is_nonstarter { 1 } else { 0 };
             Decomposition {
                 leading_nonstarters: nonstarter,
@@ -111,6 +104,7 @@ mod tests {
         classify_nonstarters,
     };
     use std::char;
+    use std::string::String;
     use normalization_tests::NORMALIZATION_TESTS;
     use normalize::decompose_compatible;
     use lookups::canonical_combining_class;
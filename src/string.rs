@@ -0,0 +1,154 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Normalization of whole strings, avoiding work for already normalized text.
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use core::fmt;
+use smallvec::SmallVec;
+use bulk::PlainRuns;
+use form::Form;
+use quick_check::normalized_prefix_len;
+
+/// Collects the normalization of a string, only allocating once it turns out
+/// to differ from the string itself.
+struct CowSink<'a> {
+    s: &'a str,
+    // The length of the prefix of `s` the normalized text matches so far.
+    matched: usize,
+    out: Option<String>,
+}
+
+impl<'a> CowSink<'a> {
+    fn finish(self) -> Cow<'a, str> {
+        match self.out {
+            Some(out) => Cow::Owned(out),
+            None if self.matched == self.s.len() => Cow::Borrowed(self.s),
+            None => Cow::Owned(self.s[..self.matched].into()),
+        }
+    }
+}
+
+impl<'a> Extend<char> for CowSink<'a> {
+    fn extend<T: IntoIterator<Item=char>>(&mut self, iter: T) {
+        for c in iter {
+            if let Some(ref mut out) = self.out {
+                out.push(c);
+            } else if self.s[self.matched..].starts_with(c) {
+                self.matched += c.len_utf8();
+            } else {
+                let mut out = String::with_capacity(self.s.len());
+                out.push_str(&self.s[..self.matched]);
+                out.push(c);
+                self.out = Some(out);
+            }
+        }
+    }
+}
+
+/// Normalize `s` into `form`, borrowing it if it's already normalized.
+///
+/// The longest prefix of `s` that's definitely normalized is skipped over,
+/// and the normalization of the remainder is compared against the string as
+/// it goes, so nothing gets allocated unless the string actually changes.
+fn normalize_cow<'a>(s: &'a str, form: Form) -> Cow<'a, str> {
+    let n = match normalized_prefix_len(PlainRuns::new(s, form), form.qc()) {
+        Some(n) => n,
+        None => return Cow::Borrowed(s),
+    };
+    let mut sink = CowSink { s, matched: n, out: None };
+    form.extend(s[n..].chars(), &mut sink);
+    sink.finish()
+}
+
+/// Returns the string in NFC, borrowing the input if it's already normalized.
+#[inline]
+pub fn nfc_cow<'a>(s: &'a str) -> Cow<'a, str> {
+    normalize_cow(s, Form::Nfc)
+}
+
+/// Returns the string in NFKC, borrowing the input if it's already normalized.
+#[inline]
+pub fn nfkc_cow<'a>(s: &'a str) -> Cow<'a, str> {
+    normalize_cow(s, Form::Nfkc)
+}
+
+/// Returns the string in NFD, borrowing the input if it's already normalized.
+#[inline]
+pub fn nfd_cow<'a>(s: &'a str) -> Cow<'a, str> {
+    normalize_cow(s, Form::Nfd)
+}
+
+/// Returns the string in NFKD, borrowing the input if it's already normalized.
+#[inline]
+pub fn nfkd_cow<'a>(s: &'a str) -> Cow<'a, str> {
+    normalize_cow(s, Form::Nfkd)
+}
+
+/// Normalize `s` into `form`, appending the result to `out`.
+///
+/// The longest prefix of `s` that's already normalized is copied over as is,
+/// and only the remainder is run through the normalization iterators.
+///
+/// ```rust
+/// use unicode_normalization::{normalize_into, Form};
+///
+/// let mut out = String::new();
+/// normalize_into("Introduccio\u{301}n", Form::Nfc, &mut out);
+/// assert_eq!(out, "Introducci\u{f3}n");
+/// ```
+pub fn normalize_into(s: &str, form: Form, out: &mut String) {
+    let n = normalized_prefix_len(PlainRuns::new(s, form), form.qc()).unwrap_or(s.len());
+    out.push_str(&s[..n]);
+    form.extend(s[n..].chars(), out);
+}
+
+/// Normalize `s` into `form`, writing the result to `out`. Like
+/// `normalize_into`, this copies over the normalized prefix of `s` in one go.
+pub(crate) fn write_normalized<W: fmt::Write>(s: &str, form: Form, out: &mut W) -> fmt::Result {
+    let n = normalized_prefix_len(PlainRuns::new(s, form), form.qc()).unwrap_or(s.len());
+    out.write_str(&s[..n])?;
+    form.write(s[n..].chars(), out)
+}
+
+/// Methods for normalizing an owned string in place.
+pub trait NormalizeInPlace {
+    /// Normalize the string into `form`, reusing its existing allocation.
+    ///
+    /// Only the part of the string after its longest normalized prefix is
+    /// rewritten: it's normalized one segment at a time onto the end of the
+    /// string, and the original text is then removed. Nothing else is
+    /// allocated unless a segment is longer than 32 characters, or the string
+    /// needs to grow.
+    fn normalize_in_place(&mut self, form: Form);
+}
+
+impl NormalizeInPlace for String {
+    fn normalize_in_place(&mut self, form: Form) {
+        let n = match normalized_prefix_len(PlainRuns::new(self, form), form.qc()) {
+            Some(n) => n,
+            None => return,
+        };
+        let end = self.len();
+        // Each segment is copied out before its normalization is appended,
+        // since the string can't be read from while it's being extended.
+        let mut segment = SmallVec::<[char; 32]>::new();
+        let mut start = n;
+        while start < end {
+            let next = form.next_boundary(&self[..end], start + 1);
+            segment.clear();
+            segment.extend(self[start..next].chars());
+            form.extend(segment.iter().cloned(), self);
+            start = next;
+        }
+        self.drain(n..end);
+    }
+}
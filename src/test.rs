@@ -10,6 +10,7 @@
 
 
 use std::char;
+use std::string::{String, ToString};
 use super::UnicodeNormalization;
 use super::char::is_combining_mark;
 
@@ -163,6 +164,8 @@ fn test_official() {
 fn test_quick_check() {
     use normalization_tests::NORMALIZATION_TESTS;
     use quick_check;
+    use std::vec::Vec;
+
     for test in NORMALIZATION_TESTS {
         assert!(quick_check::is_nfc(test.nfc));
         assert!(quick_check::is_nfd(test.nfd));
@@ -181,6 +184,372 @@ fn test_quick_check() {
             assert!(quick_check::is_nfd(test.nfkd));
         }
     }
+
+    // Checks that only renormalize around undecided characters agree with
+    // full renormalization.
+    let mut strings: Vec<String> = NORMALIZATION_TESTS.iter()
+        .flat_map(|t| vec![t.source, t.nfc, t.nfd, t.nfkc, t.nfkd])
+        .map(String::from)
+        .collect();
+    strings.push(NORMALIZATION_TESTS.iter().map(|t| t.nfc).collect());
+    strings.push(NORMALIZATION_TESTS.iter().map(|t| t.nfkc).collect());
+    strings.push(String::from("\u{e9}\u{301}x\u{1100}\u{1161}\u{ac00}\u{11a8}"));
+    for s in &strings {
+        assert_eq!(quick_check::is_nfc(s), s.chars().eq(s.nfc()), "{:?}", s);
+        assert_eq!(quick_check::is_nfkc(s), s.chars().eq(s.nfkc()), "{:?}", s);
+    }
+}
+
+#[test]
+fn test_fcd() {
+    use normalization_tests::NORMALIZATION_TESTS;
+    use normalize::decompose_canonical;
+    use quick_check::is_fcd;
+
+    for test in NORMALIZATION_TESTS {
+        assert!(is_fcd(test.nfd));
+        assert!(is_fcd(test.nfkd));
+        for &s in &[test.source, test.nfc, test.nfkc] {
+            // Exactly the FCD strings need no reordering after decomposing
+            // each character on its own.
+            let mut decomposed = String::new();
+            for ch in s.chars() {
+                decompose_canonical(ch, |d| decomposed.push(d));
+            }
+            assert_eq!(is_fcd(s), decomposed == s.nfd().collect::<String>());
+        }
+    }
+
+    assert!(is_fcd("\u{1e0d}\u{307}"));
+    assert!(!is_fcd("\u{1e0b}\u{323}"));
+    assert!(!is_fcd("a\u{301}\u{323}"));
+    assert!(is_fcd("\u{ac00}\u{11a8}"));
+}
+
+#[test]
+fn test_fcc() {
+    use normalization_tests::NORMALIZATION_TESTS;
+    use quick_check::is_fcd;
+
+    for test in NORMALIZATION_TESTS {
+        for &s in &[test.source, test.nfc, test.nfd] {
+            let fcc = s.fcc().collect::<String>();
+            assert!(is_fcd(&fcc));
+            assert_eq!(fcc.nfd().collect::<String>(), test.nfd);
+        }
+    }
+
+    assert_eq!("a\u{301}".fcc().collect::<String>(), "\u{e1}");
+    assert_eq!("s\u{323}\u{307}".fcc().collect::<String>(), "\u{1e69}");
+    assert_eq!("\u{1100}\u{1161}\u{11a8}".fcc().collect::<String>(), "\u{ac01}");
+
+    // A combining mark that doesn't compose blocks the ones after it, even
+    // though NFC would compose them.
+    assert_eq!("a\u{316}\u{301}".nfc().collect::<String>(), "\u{e1}\u{316}");
+    assert_eq!("a\u{316}\u{301}".fcc().collect::<String>(), "a\u{316}\u{301}");
+}
+
+#[test]
+fn test_nfkc_casefold() {
+    use normalization_tests::NORMALIZATION_TESTS;
+    use quick_check::{
+        IsNormalized,
+        is_nfkc,
+        is_nfkc_casefolded,
+        is_nfkc_casefolded_quick,
+        is_nfkc_simple_casefolded,
+        is_nfkc_simple_casefolded_quick,
+    };
+
+    let casefold = |s: &str| s.nfkc_casefold().collect::<String>();
+    let simple_casefold = |s: &str| s.nfkc_simple_casefold().collect::<String>();
+
+    for test in NORMALIZATION_TESTS {
+        let folded = casefold(test.source);
+        assert_eq!(casefold(test.nfc), folded);
+        assert_eq!(casefold(test.nfkd), folded);
+        assert_eq!(casefold(&folded), folded);
+        assert!(is_nfkc(&folded));
+        assert!(is_nfkc_casefolded(&folded));
+
+        let folded = simple_casefold(test.source);
+        assert_eq!(simple_casefold(test.nfkc), folded);
+        assert!(is_nfkc_simple_casefolded(&folded));
+    }
+
+    assert_eq!(casefold("Stra\u{df}e"), "strasse");
+    assert_eq!(simple_casefold("Stra\u{df}e"), "stra\u{df}e");
+    assert_eq!(casefold("\u{1e9e}"), "ss");
+    assert_eq!(simple_casefold("\u{1e9e}"), "\u{df}");
+    assert_eq!(casefold("\u{fb01}le"), "file");
+    assert_eq!(casefold("\u{212b}"), "\u{e5}");
+    assert_eq!(casefold("A\u{30a}"), "\u{e5}");
+    assert_eq!(casefold("soft\u{ad}hyphen"), "softhyphen");
+    assert_eq!(casefold("\u{2460}"), "1");
+
+    // Simple folding still reaches the uppercase letters and U+0345 COMBINING
+    // GREEK YPOGEGRAMMENI inside precomposed characters.
+    assert_eq!(simple_casefold("\u{130}"), "i\u{307}");
+    assert_eq!(simple_casefold("\u{1f80}"), "\u{1f00}\u{3b9}");
+    assert_eq!(simple_casefold("\u{1fb3}"), "\u{3b1}\u{3b9}");
+    for &s in &["\u{130}", "\u{1f80}", "\u{1fb3}"] {
+        let unchanged = simple_casefold(s) == s;
+        assert_eq!(is_nfkc_simple_casefolded(s), unchanged);
+        assert_eq!(is_nfkc_simple_casefolded_quick(s.chars()) == IsNormalized::Yes, unchanged);
+    }
+
+    assert_eq!(is_nfkc_casefolded_quick("abc".chars()), IsNormalized::Yes);
+    assert_eq!(is_nfkc_casefolded_quick("aBc".chars()), IsNormalized::No);
+    assert_eq!(is_nfkc_casefolded_quick("\u{df}".chars()), IsNormalized::No);
+    assert_eq!(is_nfkc_casefolded_quick("a\u{301}".chars()), IsNormalized::Maybe);
+    assert!(!is_nfkc_casefolded("a\u{301}"));
+    assert!(is_nfkc_simple_casefolded("\u{df}"));
+    assert!(!is_nfkc_simple_casefolded("\u{1e9e}"));
+}
+
+#[test]
+fn test_canonically_eq() {
+    use compare::{canonically_eq, compatibly_eq};
+    use normalization_tests::NORMALIZATION_TESTS;
+
+    for test in NORMALIZATION_TESTS {
+        assert!(canonically_eq(test.source, test.nfc));
+        assert!(canonically_eq(test.nfd, test.source));
+        assert!(compatibly_eq(test.source, test.nfkc));
+        assert!(compatibly_eq(test.nfkd, test.nfc));
+        assert_eq!(canonically_eq(test.source, test.nfkd), test.nfd == test.nfkd);
+    }
+
+    assert!(canonically_eq("", ""));
+    assert!(!canonically_eq("ab", "abc"));
+    assert!(!canonically_eq("\u{e9}", "\u{e8}"));
+    assert!(canonically_eq("ab\u{e7}", "abc\u{327}"));
+    assert!(!canonically_eq("abc\u{327}", "abc"));
+    assert!(canonically_eq("xa\u{323}\u{301}", "xa\u{301}\u{323}"));
+    assert!(!canonically_eq("xa\u{301}\u{300}", "xa\u{300}\u{301}"));
+    assert!(canonically_eq("\u{ac00}\u{11a8}", "\u{1100}\u{1161}\u{11a8}"));
+    assert!(!canonically_eq("\u{fb01}", "fi"));
+    assert!(compatibly_eq("\u{fb01}", "fi"));
+    assert!(compatibly_eq("x\u{2460}", "x1"));
+    assert!(!compatibly_eq("x\u{2460}", "x2"));
+}
+
+#[test]
+fn test_canonical_cmp() {
+    use core::cmp::Ordering;
+    use compare::{CanonicalOrd, canonical_cmp};
+    use normalization_tests::NORMALIZATION_TESTS;
+    use std::collections::BTreeSet;
+    use std::vec::Vec;
+
+    for test in NORMALIZATION_TESTS {
+        assert_eq!(canonical_cmp(test.source, test.nfc), Ordering::Equal);
+        assert_eq!(canonical_cmp(test.nfd, test.source), Ordering::Equal);
+    }
+    for pair in NORMALIZATION_TESTS.windows(2) {
+        let (a, b) = (pair[0].source, pair[1].source);
+        let expected = a.nfd().cmp(b.nfd());
+        assert_eq!(canonical_cmp(a, b), expected);
+        assert_eq!(canonical_cmp(b, a), expected.reverse());
+    }
+
+    assert_eq!(canonical_cmp("", "a"), Ordering::Less);
+    assert_eq!(canonical_cmp("ab", "a"), Ordering::Greater);
+    // U+00E9 sorts after "f" by its code point, but not by its decomposition.
+    assert_eq!("\u{e9}".cmp("f"), Ordering::Greater);
+    assert_eq!(canonical_cmp("\u{e9}", "f"), Ordering::Less);
+    assert_eq!(canonical_cmp("\u{e9}", "e"), Ordering::Greater);
+    assert_eq!(canonical_cmp("xa\u{323}\u{301}", "xa\u{301}\u{323}"), Ordering::Equal);
+    assert_eq!(canonical_cmp("xa\u{301}\u{300}", "xa\u{300}\u{301}"), Ordering::Greater);
+
+    let set: BTreeSet<_> = ["\u{e9}", "e\u{301}", "f", "e", "\u{1e15}", "e\u{304}\u{300}"]
+        .iter()
+        .map(|&s| CanonicalOrd(s))
+        .collect();
+    let sorted: Vec<String> = set.iter().map(|k| k.0.nfc().collect()).collect();
+    assert_eq!(sorted, ["e", "\u{e9}", "\u{1e15}", "f"]);
+}
+
+#[test]
+fn test_hash_normalized() {
+    use form::Form;
+    use hash::{NfcKey, NfcKeyStr, NormalizingHasher, hash_normalized};
+    use normalization_tests::NORMALIZATION_TESTS;
+    use std::collections::HashMap;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let hash = |s: &str, form: Form| {
+        let mut hasher = DefaultHasher::new();
+        hash_normalized(s, form, &mut hasher);
+        hasher.finish()
+    };
+
+    for test in NORMALIZATION_TESTS {
+        for &form in &[Form::Nfc, Form::Nfd] {
+            let h = hash(test.source, form);
+            assert_eq!(hash(test.nfc, form), h);
+            assert_eq!(hash(test.nfd, form), h);
+        }
+        for &form in &[Form::Nfkc, Form::Nfkd] {
+            let h = hash(test.source, form);
+            assert_eq!(hash(test.nfkc, form), h);
+            assert_eq!(hash(test.nfkd, form), h);
+        }
+    }
+    assert_ne!(hash("\u{e9}", Form::Nfc), hash("e", Form::Nfc));
+    assert_ne!(hash("\u{fb01}", Form::Nfc), hash("fi", Form::Nfc));
+    assert_eq!(hash("\u{fb01}", Form::Nfkc), hash("fi", Form::Nfkc));
+
+    // The hasher adapter hashes strings as `hash_normalized` does, and
+    // leaves everything else alone.
+    let hash_with = |key: &(&str, u32, String), form: Form| {
+        let mut hasher = NormalizingHasher::new(DefaultHasher::new(), form);
+        key.hash(&mut hasher);
+        hasher.finish()
+    };
+    for test in NORMALIZATION_TESTS {
+        let key = (test.source, 0xcc81, String::from(test.nfkd));
+        let mut hasher = DefaultHasher::new();
+        hash_normalized(test.nfc, Form::Nfc, &mut hasher);
+        hasher.write_u32(0xcc81);
+        hash_normalized(test.nfkc, Form::Nfc, &mut hasher);
+        assert_eq!(hash_with(&key, Form::Nfc), hasher.finish());
+        assert_eq!(hash_with(&key, Form::Nfkc),
+                   hash_with(&(test.nfkd, 0xcc81, String::from(test.nfkc)), Form::Nfkc));
+    }
+    assert_ne!(hash_with(&("\u{e9}", 1, String::new()), Form::Nfc),
+               hash_with(&("\u{e9}", 2, String::new()), Form::Nfc));
+
+    let mut map = HashMap::new();
+    map.insert(NfcKey::from("Am\u{e9}lie"), 1);
+    map.insert(NfcKey::from(String::from("\u{1e9b}\u{323}")), 2);
+    assert_eq!(map.get(NfcKeyStr::new("Ame\u{301}lie")), Some(&1));
+    assert_eq!(map.get(NfcKeyStr::new("\u{17f}\u{323}\u{307}")), Some(&2));
+    assert_eq!(map.get(NfcKeyStr::new("Amelie")), None);
+    assert_eq!(map.insert(NfcKey::from("Ame\u{301}lie"), 3), Some(1));
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.keys().find(|k| k.as_str().starts_with("Am")).unwrap().as_str(), "Am\u{e9}lie");
+}
+
+#[test]
+fn test_caseless() {
+    use caseless::{CaseFolding, canonical_caseless_eq, compatibility_caseless_eq};
+    use normalization_tests::NORMALIZATION_TESTS;
+
+    for test in NORMALIZATION_TESTS {
+        assert!(canonical_caseless_eq(test.source, test.nfc, CaseFolding::Default));
+        assert!(canonical_caseless_eq(test.source, test.nfd, CaseFolding::Default));
+        assert!(compatibility_caseless_eq(test.source, test.nfkc, CaseFolding::Default));
+        assert!(compatibility_caseless_eq(test.nfc, test.nfkd, CaseFolding::Default));
+    }
+
+    assert!(canonical_caseless_eq("Stra\u{df}e", "STRASSE", CaseFolding::Default));
+    assert!(canonical_caseless_eq("\u{1e9e}", "ss", CaseFolding::Default));
+    assert!(canonical_caseless_eq("\u{212b}", "\u{e5}", CaseFolding::Default));
+    assert!(canonical_caseless_eq("\u{1f80}", "\u{1f08}\u{345}", CaseFolding::Default));
+    assert!(!canonical_caseless_eq("\u{1c5}", "d\u{17e}", CaseFolding::Default));
+    assert!(compatibility_caseless_eq("\u{1c5}", "d\u{17e}", CaseFolding::Default));
+    assert!(!canonical_caseless_eq("\u{2460}", "1", CaseFolding::Default));
+    assert!(compatibility_caseless_eq("\u{2460}", "1", CaseFolding::Default));
+    assert!(!compatibility_caseless_eq("a", "b", CaseFolding::Default));
+
+    // U+3392 SQUARE MHZ only folds completely on the second round.
+    assert!(compatibility_caseless_eq("\u{3392}", "mhz", CaseFolding::Default));
+    assert_eq!("\u{3392}".compatibility_caseless(CaseFolding::Default).collect::<String>(), "mhz");
+
+    // The dotted and dotless I.
+    let turkic = |s: &str| s.canonical_caseless(CaseFolding::Turkic).collect::<String>();
+    let default = |s: &str| s.canonical_caseless(CaseFolding::Default).collect::<String>();
+    assert_eq!(default("I"), "i");
+    assert_eq!(default("\u{130}"), "i\u{307}");
+    assert_eq!(turkic("I"), "\u{131}");
+    assert_eq!(turkic("\u{130}"), "i");
+    assert_eq!(turkic("I\u{307}"), "i");
+    assert_eq!(turkic("I\u{316}\u{307}"), "i\u{316}");
+    assert_eq!(turkic("I\u{301}\u{307}"), "\u{131}\u{301}\u{307}");
+    assert_eq!(turkic("II\u{307}I"), "\u{131}i\u{131}");
+    assert_eq!(turkic("\u{130}STANBUL"), "istanbul");
+    assert_eq!("\u{130}".compatibility_caseless(CaseFolding::Turkic).collect::<String>(), "i");
+    assert!(canonical_caseless_eq("\u{130}STANBUL", "istanbul", CaseFolding::Turkic));
+    assert!(!canonical_caseless_eq("\u{130}STANBUL", "istanbul", CaseFolding::Default));
+    assert!(!canonical_caseless_eq("I", "i", CaseFolding::Turkic));
+    assert!(compatibility_caseless_eq("\u{130}", "i", CaseFolding::Turkic));
+    assert!(!compatibility_caseless_eq("\u{130}", "i", CaseFolding::Default));
+}
+
+#[test]
+fn test_boundaries() {
+    use form::Form;
+    use normalization_tests::NORMALIZATION_TESTS;
+    use std::collections::BTreeSet;
+    use std::vec::Vec;
+
+    let normalize = |s: &str, form: Form| {
+        let mut out = String::new();
+        form.extend(s.chars(), &mut out);
+        out
+    };
+    let source: String = NORMALIZATION_TESTS.iter().map(|t| t.source).collect();
+    let chars: BTreeSet<char> = source.chars().collect();
+    let probes = ['a', 'e', '\u{301}', '\u{323}', '\u{338}', '\u{345}', '\u{1100}', '\u{1161}',
+                  '\u{11a8}', '\u{ac00}', '\u{3099}', '\u{b3e}', '\u{fb01}', '\u{1d15e}'];
+
+    for &form in &[Form::Nfc, Form::Nfd, Form::Nfkc, Form::Nfkd] {
+        let segments: Vec<&str> = form.segments(&source).collect();
+        assert_eq!(segments.concat(), source);
+        let pieces: Vec<String> = segments.iter().map(|s| normalize(s, form)).collect();
+        assert_eq!(pieces.concat(), normalize(&source, form));
+
+        let mut start = 0;
+        for segment in segments {
+            let end = start + segment.len();
+            assert_eq!(form.prev_boundary(&source, start), start);
+            assert_eq!(form.next_boundary(&source, start), start);
+            for i in start + 1..end {
+                assert_eq!(form.prev_boundary(&source, i), start);
+                assert_eq!(form.next_boundary(&source, i), end);
+            }
+            start = end;
+        }
+        assert_eq!(form.prev_boundary(&source, source.len() + 1), source.len());
+
+        for &c in &chars {
+            let mut c_str = String::new();
+            c_str.push(c);
+            let c_normalized = normalize(&c_str, form);
+            if form.is_inert(c) {
+                assert_eq!(c_normalized, c_str);
+            }
+            for &p in &probes {
+                let mut p_str = String::new();
+                p_str.push(p);
+                let split = c_normalized.clone() + &normalize(&p_str, form);
+                if form.has_boundary_after(c) {
+                    assert_eq!(normalize(&(c_str.clone() + &p_str), form), split);
+                }
+                if form.has_boundary_before(c) {
+                    let split = normalize(&p_str, form) + &c_normalized;
+                    assert_eq!(normalize(&(p_str + &c_str), form), split);
+                }
+            }
+        }
+    }
+    assert_eq!(Form::Nfc.segments("").next(), None);
+
+    // A vowel composes with a leading consonant into a syllable that takes a
+    // trailing one.
+    let segments: Vec<_> = Form::Nfc.segments("\u{1100}\u{1100}\u{1161}\u{11a8}").collect();
+    assert_eq!(segments, ["\u{1100}", "\u{1100}\u{1161}\u{11a8}"]);
+    let segments: Vec<_> = Form::Nfc.segments("\u{dd9}\u{dcf}\u{dca}").collect();
+    assert_eq!(segments, ["\u{dd9}\u{dcf}\u{dca}"]);
+
+    // A single segment can take up the whole string.
+    let s = "a".to_string() + &"\u{301}".repeat(40);
+    let segments = Form::Nfc.segments(&s);
+    assert_eq!(segments.size_hint(), (1, Some(81)));
+    assert_eq!(segments.count(), 1);
+    assert_eq!(Form::Nfc.segments("").size_hint(), (0, Some(0)));
 }
 
 #[test]
@@ -201,3 +570,395 @@ fn test_is_combining_mark_misc() {
     // Category: Mark, Nonspacing [Mn]
     assert!(is_combining_mark('\u{11C3F}'));
 }
+
+#[test]
+fn test_cow() {
+    use normalization_tests::NORMALIZATION_TESTS;
+    use std::borrow::Cow;
+    use string::{nfc_cow, nfd_cow, nfkc_cow, nfkd_cow};
+
+    fn is_borrowed(c: Cow<str>) -> bool {
+        match c {
+            Cow::Borrowed(_) => true,
+            Cow::Owned(_) => false,
+        }
+    }
+
+    for test in NORMALIZATION_TESTS {
+        assert!(is_borrowed(nfc_cow(test.nfc)));
+        assert!(is_borrowed(nfd_cow(test.nfd)));
+        assert!(is_borrowed(nfkc_cow(test.nfkc)));
+        assert!(is_borrowed(nfkd_cow(test.nfkd)));
+        assert_eq!(is_borrowed(nfc_cow(test.source)), test.source == test.nfc);
+
+        assert_eq!(nfc_cow(test.source), test.nfc);
+
+        assert_eq!(nfd_cow(test.source), test.nfd);
+        assert_eq!(nfkc_cow(test.source), test.nfkc);
+        assert_eq!(nfkd_cow(test.source), test.nfkd);
+    }
+
+    assert!(is_borrowed(nfc_cow("x\u{301}")));
+    assert!(is_borrowed(nfc_cow("\u{ac00}\u{1100}")));
+    assert_eq!(nfc_cow("abc\u{1100}\u{1161}"), "abc\u{ac00}");
+    assert_eq!(nfc_cow("abc\u{1100}\u{1161}x"), "abc\u{ac00}x");
+    assert_eq!(nfd_cow("abc\u{ac00}x"), "abc\u{1100}\u{1161}x");
+}
+
+#[test]
+fn test_normalize_into() {
+    use normalization_tests::NORMALIZATION_TESTS;
+    use form::Form;
+    use string::{NormalizeInPlace, normalize_into};
+
+    for test in NORMALIZATION_TESTS {
+        for &(form, expected) in &[(Form::Nfc, test.nfc), (Form::Nfd, test.nfd),
+                                   (Form::Nfkc, test.nfkc), (Form::Nfkd, test.nfkd)] {
+            for &s in &[test.source, test.nfc, test.nfd] {
+                let mut out = String::from("x");
+                normalize_into(s, form, &mut out);
+                assert_eq!(&out[1..], expected);
+
+                let mut owned = String::from(s);
+                owned.normalize_in_place(form);
+                assert_eq!(owned, expected);
+            }
+        }
+    }
+
+    let mut s = String::from("ascii prefix then a\u{301}\u{1161}");
+    s.normalize_in_place(Form::Nfc);
+    assert_eq!(s, "ascii prefix then \u{e1}\u{1161}");
+
+    let mut s = String::from("\u{1100}\u{1161}\u{11a8}");
+    s.normalize_in_place(Form::Nfc);
+    assert_eq!(s, "\u{ac01}");
+
+    // Several segments, one of them longer than the copy buffer, normalized
+    // without moving the string.
+    let long = "a".to_string() + &"\u{301}".repeat(40);
+    let mut s = String::with_capacity(1024);
+    s.push_str("x\u{307}y\u{327}\u{301}z");
+    s.push_str(&long);
+    let ptr = s.as_ptr();
+    s.normalize_in_place(Form::Nfc);
+    assert_eq!(s, "\u{1e8b}\u{fd}\u{327}z\u{e1}".to_string() + &"\u{301}".repeat(39));
+    assert_eq!(s.as_ptr(), ptr);
+}
+
+
+#[test]
+fn test_normalizer() {
+    use normalization_tests::NORMALIZATION_TESTS;
+    use form::Form;
+    use normalizer::Normalizer;
+    use std::fmt;
+    use string::normalize_into;
+
+    // Pushing `s` split at any character boundary, including ones inside a
+    // segment, gives the same result as normalizing it in one go.
+    let check = |s: &str, form: Form| {
+        let mut expected = String::new();
+        normalize_into(s, form, &mut expected);
+        for (i, _) in s.char_indices().skip(1) {
+            let mut normalizer = Normalizer::new(form);
+            let mut out = String::new();
+            normalizer.push(&s[..i], &mut out).unwrap();
+            normalizer.push(&s[i..], &mut out).unwrap();
+            normalizer.finish(&mut out).unwrap();
+            assert_eq!(out, expected, "{:?} split at {}", s, i);
+        }
+    };
+    for test in NORMALIZATION_TESTS {
+        for &form in &[Form::Nfc, Form::Nfd, Form::Nfkc, Form::Nfkd] {
+            check(test.source, form);
+        }
+    }
+
+    // Only what a later chunk can't change goes out.
+    let mut normalizer = Normalizer::new(Form::Nfc);
+    let mut out = String::new();
+    normalizer.push("\u{1100}", &mut out).unwrap();
+    normalizer.push("\u{1161}", &mut out).unwrap();
+    normalizer.push("\u{11a8}a\u{323}", &mut out).unwrap();
+    assert_eq!(out, "\u{ac01}");
+    normalizer.push("\u{307}", &mut out).unwrap();
+    normalizer.finish(&mut out).unwrap();
+    assert_eq!(out, "\u{ac01}\u{1ea1}\u{307}");
+
+    // A run of nonstarters split across chunks is still reordered.
+    let mut out = String::new();
+    normalizer.push("a\u{301}", &mut out).unwrap();
+    normalizer.push_char('\u{323}', &mut out).unwrap();
+    assert_eq!(out, "");
+    normalizer.finish(&mut out).unwrap();
+    assert_eq!(out, "\u{1ea1}\u{301}");
+
+    // The normalizer starts over after `finish`.
+    normalizer.push("\u{301}", &mut out).unwrap();
+    normalizer.finish(&mut out).unwrap();
+    assert_eq!(out, "\u{1ea1}\u{301}\u{301}");
+
+    // A sink failing on its first write leaves the normalizer as it was, so
+    // the call can be retried.
+    struct FailOnce(bool, String);
+
+    impl fmt::Write for FailOnce {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            if self.0 {
+                self.0 = false;
+                return Err(fmt::Error);
+            }
+            self.1.push_str(s);
+            Ok(())
+        }
+    }
+
+    let mut normalizer = Normalizer::new(Form::Nfc);
+    let mut sink = FailOnce(false, String::new());
+    normalizer.push("xe", &mut sink).unwrap();
+    sink.0 = true;
+    assert!(normalizer.push("\u{301}yz", &mut sink).is_err());
+    normalizer.push("\u{301}yz", &mut sink).unwrap();
+    sink.0 = true;
+    assert!(normalizer.finish(&mut sink).is_err());
+    normalizer.finish(&mut sink).unwrap();
+    assert_eq!(sink.1, "x\u{e9}yz");
+}
+
+#[test]
+fn test_normalized_string() {
+    use normalization_tests::NORMALIZATION_TESTS;
+    use normalized_string::{NfcStr, NfcString, NfdString, NfkcString, NfkdString};
+    use std::borrow::ToOwned;
+
+    // Appending renormalizes the end of the string it might change, wherever
+    // the text is split.
+    for test in NORMALIZATION_TESTS {
+        let s = test.source;
+        for (i, c) in s.char_indices().skip(1) {
+            let mut nfc = NfcString::from(&s[..i]);
+            nfc.push_str(&s[i..]);
+            assert_eq!(nfc.as_str(), test.nfc, "{:?} split at {}", s, i);
+
+            let mut nfd = NfdString::from(&s[..i]);
+            nfd.push(c);
+            nfd.push_str(&s[i + c.len_utf8()..]);
+            assert_eq!(nfd.as_str(), test.nfd, "{:?} split at {}", s, i);
+
+            let mut nfkc = NfkcString::from(&s[..i]);
+            nfkc.extend(s[i..].chars());
+            assert_eq!(nfkc.as_str(), test.nfkc, "{:?} split at {}", s, i);
+
+            let mut nfkd = NfkdString::from(&s[..i]);
+            nfkd.extend(Some(&s[i..]));
+            assert_eq!(nfkd.as_str(), test.nfkd, "{:?} split at {}", s, i);
+        }
+    }
+
+    let mut s = NfcString::from("e");
+    s.push('\u{323}');
+    assert_eq!(s.as_str(), "\u{1eb9}");
+    s.push('\u{302}');
+    assert_eq!(s.as_str(), "\u{1ec7}");
+    s.push_str("\u{1100}");
+    s.push('\u{1161}');
+    assert_eq!(s.as_str(), "\u{1ec7}\u{ac00}");
+
+    let s = NfcString::from("a\u{323}\u{307}");
+    assert_eq!(s.as_str(), "\u{1ea1}\u{307}");
+    assert_eq!(NfcString::from(String::from("a\u{301}")).as_str(), "\u{e1}");
+    assert_eq!(NfdString::from("\u{e1}").as_str(), "a\u{301}");
+    assert_eq!("\u{fb01}".chars().collect::<NfkcString>().as_str(), "fi");
+
+    assert!(NfcString::from_normalized(String::from("a\u{301}")).is_err());
+    assert_eq!(NfcString::from_normalized(String::from("\u{e1}")), Ok(NfcString::from("\u{e1}")));
+    assert!(NfcStr::new("a\u{301}").is_none());
+    let borrowed = NfcStr::new("\u{e1}b").unwrap();
+    assert_eq!(borrowed.len(), 3);
+    assert_eq!(&**s, "\u{1ea1}\u{307}");
+    assert_eq!(borrowed.to_owned().into_string(), "\u{e1}b");
+}
+
+#[test]
+fn test_normalized_indices() {
+    use normalization_tests::NORMALIZATION_TESTS;
+    use core::ops::Range;
+    use form::Form;
+    use indices::NormalizedIndices;
+    use std::vec::Vec;
+
+    let indices = |s: &str, form: Form| -> Vec<(char, Range<usize>)> {
+        NormalizedIndices::new(s, form).collect()
+    };
+
+    // Reordering moves characters along with their ranges, also when they
+    // come from the decomposition of another character.
+    assert_eq!(indices("a\u{301}\u{323}", Form::Nfd), [('a', 0..1), ('\u{323}', 3..5), ('\u{301}', 1..3)]);
+    assert_eq!(indices("\u{e9}\u{323}", Form::Nfd), [('e', 0..2), ('\u{323}', 2..4), ('\u{301}', 0..2)]);
+    assert_eq!(indices("\u{1e0b}\u{323}", Form::Nfd), [('d', 0..3), ('\u{323}', 3..5), ('\u{307}', 0..3)]);
+    // A mark reordered before another composes first, and the composite
+    // covers both its ranges; the mark left behind keeps its own.
+    assert_eq!(indices("a\u{301}\u{323}", Form::Nfc), [('\u{1ea1}', 0..5), ('\u{301}', 1..3)]);
+    assert_eq!(indices("\u{1e0b}\u{323}", Form::Nfc), [('\u{1e0d}', 0..5), ('\u{307}', 0..3)]);
+    assert_eq!(indices("e\u{323}x\u{302}", Form::Nfc), [('\u{1eb9}', 0..3), ('x', 3..4), ('\u{302}', 4..6)]);
+
+    assert_eq!(indices("", Form::Nfc), []);
+    assert_eq!(indices("e\u{301}x", Form::Nfc), [('\u{e9}', 0..3), ('x', 3..4)]);
+    assert_eq!(indices("\u{e9}x", Form::Nfd), [('e', 0..2), ('\u{301}', 0..2), ('x', 2..3)]);
+    assert_eq!(indices("\u{1100}\u{1161}\u{11a8}", Form::Nfc), [('\u{ac01}', 0..9)]);
+    assert_eq!(indices("\u{fb01}", Form::Nfkc), [('f', 0..3), ('i', 0..3)]);
+    assert_eq!(indices("\u{fb01}", Form::Nfc), [('\u{fb01}', 0..3)]);
+
+    // Composition can merge several characters into one.
+    let s = "\u{1100}\u{1161}\u{11a8}".repeat(5);
+    assert!(NormalizedIndices::new(&s, Form::Nfc).size_hint().0 <= 5);
+    assert!(NormalizedIndices::new(&s, Form::Nfd).size_hint().0 >= 4);
+
+    // Every range is a slice of the source that the character came from:
+    // the decomposition of the slice holds all of the character's.
+    for test in NORMALIZATION_TESTS {
+        let s = test.source;
+        for &(form, expected) in &[(Form::Nfc, test.nfc), (Form::Nfd, test.nfd),
+                                   (Form::Nfkc, test.nfkc), (Form::Nfkd, test.nfkd)] {
+            let decomposed = |s: &str| -> String {
+                match form {
+                    Form::Nfc | Form::Nfd => s.nfd().collect(),
+                    Form::Nfkc | Form::Nfkd => s.nfkd().collect(),
+                }
+            };
+            let mapped = indices(s, form);
+            assert_eq!(mapped.iter().map(|&(c, _)| c).collect::<String>(), expected);
+            assert!(NormalizedIndices::new(s, form).size_hint().0 <= mapped.len());
+            for (c, range) in mapped {
+                let source = decomposed(&s[range]);
+                let mut buf = [0; 4];
+                assert!(decomposed(c.encode_utf8(&mut buf)).chars().all(|d| source.contains(d)),
+                        "{:?} in {:?}", c, s);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_par_normalize() {
+    use normalization_tests::NORMALIZATION_TESTS;
+    use form::Form;
+    use parallel::{normalize_chunked, split};
+    use std::vec::Vec;
+    use string::normalize_into;
+
+    let s = "a\u{301}\u{301}\u{301}\u{301}b";
+    assert_eq!(split(s, Form::Nfc, 2), ["a\u{301}\u{301}\u{301}\u{301}", "b"]);
+    assert_eq!(split(s, Form::Nfc, 100), [s]);
+    assert_eq!(split("", Form::Nfc, 2), Vec::<&str>::new());
+
+    // Pieces are only cut at normalization boundaries, wherever the chunk
+    // length falls, so normalizing them separately changes nothing.
+    let source: String = NORMALIZATION_TESTS.iter().take(200).map(|t| t.source).collect();
+    for &form in &[Form::Nfc, Form::Nfd, Form::Nfkc, Form::Nfkd] {
+        let mut expected = String::new();
+        normalize_into(&source, form, &mut expected);
+        for chunk_len in 1..40 {
+            let pieces = split(&source, form, chunk_len);
+            assert_eq!(pieces.concat(), source);
+            for pair in pieces.windows(2) {
+                let before = pair[0].chars().next_back().unwrap();
+                let after = pair[1].chars().next().unwrap();
+                assert!(form.is_boundary_between(before, after));
+            }
+            assert_eq!(normalize_chunked(&source, form, chunk_len), expected);
+        }
+    }
+}
+
+#[cfg(feature = "stream")]
+mod stream {
+    use std::string::String;
+    use std::vec::Vec;
+    use core::pin::Pin;
+    use core::ptr;
+    use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+    use futures_core::Stream;
+    use form::Form;
+    use normalization_tests::NORMALIZATION_TESTS;
+    use stream::NormalizedStream;
+    use string::normalize_into;
+
+    /// A stream of `items` that's pending before each of them.
+    struct Items<T> {
+        items: Vec<T>,
+        ready: bool,
+    }
+
+    impl<T: Unpin> Stream for Items<T> {
+        type Item = T;
+
+        fn poll_next(mut self: Pin<&mut Self>, _: &mut Context) -> Poll<Option<T>> {
+            if !self.ready {
+                self.ready = true;
+                return Poll::Pending;
+            }
+            self.ready = false;
+            if self.items.is_empty() {
+                Poll::Ready(None)
+            } else {
+                Poll::Ready(Some(self.items.remove(0)))
+            }
+        }
+    }
+
+    fn items<T>(items: Vec<T>) -> Items<T> {
+        Items { items, ready: false }
+    }
+
+    fn noop_raw_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            noop_raw_waker()
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(ptr::null(), &VTABLE)
+    }
+
+    /// Poll `stream` to completion, collecting its items.
+    #[allow(unsafe_code)]
+    fn collect<S: Stream + Unpin>(mut stream: S) -> Vec<S::Item> {
+        // SAFETY: none of the vtable's functions use the data pointer.
+        let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+        let mut cx = Context::from_waker(&waker);
+        let mut out = Vec::new();
+        loop {
+            match Pin::new(&mut stream).poll_next(&mut cx) {
+                Poll::Ready(Some(item)) => out.push(item),
+                Poll::Ready(None) => return out,
+                Poll::Pending => (),
+            }
+        }
+    }
+
+    #[test]
+    fn test_normalized_stream() {
+        // An item ending anywhere inside a segment gives the same text as
+        // normalizing it in one go, and never an empty chunk.
+        for test in NORMALIZATION_TESTS {
+            let s = test.source;
+            for &form in &[Form::Nfc, Form::Nfd, Form::Nfkc, Form::Nfkd] {
+                let mut expected = String::new();
+                normalize_into(s, form, &mut expected);
+                for (i, _) in s.char_indices().skip(1) {
+                    let chunks = collect(NormalizedStream::new(items(vec![&s[..i], &s[i..]]), form));
+                    assert!(chunks.iter().all(|c| !c.is_empty()));
+                    assert_eq!(chunks.concat(), expected, "{:?} split at {}", s, i);
+                }
+            }
+        }
+
+        // What the next item might change is held back until it comes.
+        let frames = items(vec!["xe", "\u{301}"]);
+        assert_eq!(collect(NormalizedStream::new(frames, Form::Nfc)), ["x", "\u{e9}"]);
+        let frames = items(vec!["a\u{301}", "\u{323}", "", "b\u{30a}"]);
+        assert_eq!(collect(NormalizedStream::new(frames, Form::Nfc)), ["\u{1ea1}\u{301}", "b\u{30a}"]);
+    }
+}
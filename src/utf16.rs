@@ -0,0 +1,462 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Normalization of UTF-16 code units.
+//!
+//! Lone surrogates either make the input invalid (the `utf16` functions), or
+//! are passed through unchanged (the `wtf16` functions). In the latter case
+//! they are treated like any other starter that doesn't compose, so
+//! characters are never reordered or composed across them.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::char::{self, DecodeUtf16};
+use core::fmt;
+#[cfg(feature = "std")]
+use std::error::Error;
+use form::Form;
+use quick_check::{IsNormalized, quick_check};
+#[cfg(feature = "alloc")]
+use quick_check::normalized_prefix_len;
+
+/// An error returned when the input contains a lone surrogate.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Utf16Error {
+    valid_up_to: usize,
+}
+
+impl Utf16Error {
+    /// Returns the offset, in code units, of the first lone surrogate in the
+    /// input.
+    pub fn valid_up_to(&self) -> usize {
+        self.valid_up_to
+    }
+}
+
+impl fmt::Display for Utf16Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "lone surrogate at code unit offset {}", self.valid_up_to)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for Utf16Error {}
+
+/// Iterator over the characters of UTF-16 code units and their offsets,
+/// which stops at the first lone surrogate.
+#[derive(Clone)]
+struct Utf16CharIndices<'a> {
+    units: &'a [u16],
+    pos: usize,
+}
+
+impl<'a> Utf16CharIndices<'a> {
+    fn new(units: &'a [u16]) -> Self {
+        Utf16CharIndices { units, pos: 0 }
+    }
+
+    /// The offset of the next code unit to decode. Once the iterator is
+    /// exhausted, this is either the length of the input or the offset of
+    /// its first lone surrogate.
+    fn offset(&self) -> usize {
+        self.pos
+    }
+}
+
+impl<'a> Iterator for Utf16CharIndices<'a> {
+    type Item = (usize, char);
+
+    #[inline]
+    fn next(&mut self) -> Option<(usize, char)> {
+        let start = self.pos;
+        let u = match self.units.get(start) {
+            Some(&u) => u as u32,
+            None => return None,
+        };
+        let c = match (u, self.units.get(start + 1)) {
+            (0xd800..=0xdbff, Some(&v @ 0xdc00..=0xdfff)) => {
+                0x10000 + ((u - 0xd800) << 10) + (v as u32 - 0xdc00)
+            }
+            (0xd800..=0xdfff, _) => return None,
+            _ => u,
+        };
+        self.pos += if c > 0xffff { 2 } else { 1 };
+        char::from_u32(c).map(|ch| (start, ch))
+    }
+}
+
+/// An iterator adapter decoding UTF-16 code units into characters, with each
+/// lone surrogate replaced by U+FFFD REPLACEMENT CHARACTER.
+///
+/// Together with `EncodeUtf16` this normalizes UTF-16 text one code unit at
+/// a time:
+///
+/// ```rust
+/// use unicode_normalization::{EncodeUtf16, UnicodeNormalization, Utf16Chars};
+///
+/// let units = [0x61, 0x301, 0xd800, 0xd834, 0xdd5e];
+/// let chars = Utf16Chars::new(units.iter().cloned());
+/// let nfc: Vec<u16> = EncodeUtf16::new(chars.nfc()).collect();
+/// assert_eq!(nfc, [0xe1, 0xfffd, 0xd834, 0xdd57, 0xd834, 0xdd65]);
+/// ```
+#[derive(Clone)]
+pub struct Utf16Chars<I: Iterator<Item=u16>> {
+    iter: DecodeUtf16<I>,
+}
+
+impl<I: Iterator<Item=u16>> Utf16Chars<I> {
+    /// Creates an adapter decoding `units`.
+    #[inline]
+    pub fn new<T: IntoIterator<IntoIter=I, Item=u16>>(units: T) -> Utf16Chars<I> {
+        Utf16Chars { iter: char::decode_utf16(units) }
+    }
+}
+
+impl<I: Iterator<Item=u16>> Iterator for Utf16Chars<I> {
+    type Item = char;
+
+    #[inline]
+    fn next(&mut self) -> Option<char> {
+        self.iter.next().map(|r| r.unwrap_or(char::REPLACEMENT_CHARACTER))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// An iterator adapter encoding characters as UTF-16 code units.
+///
+/// See `Utf16Chars` for an example.
+#[derive(Clone)]
+pub struct EncodeUtf16<I> {
+    iter: I,
+    // The low surrogate of the last character, if it's still to come.
+    low_surrogate: Option<u16>,
+}
+
+impl<I: Iterator<Item=char>> EncodeUtf16<I> {
+    /// Creates an adapter encoding `chars`.
+    #[inline]
+    pub fn new<T: IntoIterator<IntoIter=I, Item=char>>(chars: T) -> EncodeUtf16<I> {
+        EncodeUtf16 { iter: chars.into_iter(), low_surrogate: None }
+    }
+}
+
+impl<I: Iterator<Item=char>> Iterator for EncodeUtf16<I> {
+    type Item = u16;
+
+    #[inline]
+    fn next(&mut self) -> Option<u16> {
+        if let Some(u) = self.low_surrogate.take() {
+            return Some(u);
+        }
+        let ch = self.iter.next()?;
+        let mut buf = [0; 2];
+        let units = ch.encode_utf16(&mut buf);
+        if units.len() == 2 {
+            self.low_surrogate = Some(units[1]);
+        }
+        Some(units[0])
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let pending = self.low_surrogate.is_some() as usize;
+        let (lower, upper) = self.iter.size_hint();
+        let upper = upper.and_then(|n| n.checked_mul(2)).and_then(|n| n.checked_add(pending));
+        (lower + pending, upper)
+    }
+}
+
+/// Adapter for appending characters to a buffer as UTF-16.
+#[cfg(feature = "alloc")]
+struct Utf16Sink<'a>(&'a mut Vec<u16>);
+
+#[cfg(feature = "alloc")]
+impl<'a> Extend<char> for Utf16Sink<'a> {
+    #[inline]
+    fn extend<T: IntoIterator<Item=char>>(&mut self, iter: T) {
+        let mut buf = [0; 2];
+        for ch in iter {
+            self.0.extend_from_slice(ch.encode_utf16(&mut buf));
+        }
+    }
+}
+
+/// Find the end of the normalized prefix of `input` that can be copied over
+/// verbatim. This never extends past the first lone surrogate.
+#[cfg(feature = "alloc")]
+fn copyable_prefix_len(input: &[u16], form: Form) -> usize {
+    let mut iter = Utf16CharIndices::new(input);
+    match normalized_prefix_len(iter.by_ref(), form.qc()) {
+        Some(n) => n,
+        None => iter.offset(),
+    }
+}
+
+fn first_lone_surrogate(input: &[u16], from: usize) -> Option<usize> {
+    let mut iter = Utf16CharIndices::new(&input[from..]);
+    iter.by_ref().count();
+    if iter.offset() < input.len() - from {
+        Some(from + iter.offset())
+    } else {
+        None
+    }
+}
+
+/// Normalize `input` up to its end, stopping at the first lone surrogate
+/// unless `pass_through` is set.
+#[cfg(feature = "alloc")]
+fn normalize(input: &[u16], form: Form, output: &mut Vec<u16>, pass_through: bool)
+    -> Result<(), Utf16Error>
+{
+    let mut start = 0;
+    loop {
+        let rest = &input[start..];
+        let n = copyable_prefix_len(rest, form);
+        output.extend_from_slice(&rest[..n]);
+
+        let mut iter = Utf16CharIndices::new(&rest[n..]);
+        form.extend(iter.by_ref().map(|(_, ch)| ch), &mut Utf16Sink(output));
+
+        let end = start + n + iter.offset();
+        if end == input.len() {
+            return Ok(());
+        }
+        if !pass_through {
+            return Err(Utf16Error { valid_up_to: end });
+        }
+        output.push(input[end]);
+        start = end + 1;
+    }
+}
+
+/// Quick check `input` up to its end, stopping at the first lone surrogate
+/// unless `pass_through` is set.
+fn quick_check_segments(input: &[u16], form: Form, pass_through: bool)
+    -> Result<IsNormalized, Utf16Error>
+{
+    let mut result = IsNormalized::Yes;
+    let mut start = 0;
+    loop {
+        let mut iter = Utf16CharIndices::new(&input[start..]);
+        match quick_check(iter.by_ref().map(|(_, ch)| ch), form.qc(), false) {
+            IsNormalized::Yes => (),
+            IsNormalized::Maybe => result = IsNormalized::Maybe,
+            IsNormalized::No => {
+                if !pass_through {
+                    if let Some(i) = first_lone_surrogate(input, start + iter.offset()) {
+                        return Err(Utf16Error { valid_up_to: i });
+                    }
+                }
+                return Ok(IsNormalized::No);
+            }
+        }
+
+        let end = start + iter.offset();
+        if end == input.len() {
+            return Ok(result);
+        }
+        if !pass_through {
+            return Err(Utf16Error { valid_up_to: end });
+        }
+        start = end + 1;
+    }
+}
+
+/// Normalize UTF-16 encoded `input` into `form`, appending the UTF-16
+/// encoded result to `output`.
+///
+/// If `input` contains a lone surrogate, `output` is left unchanged and the
+/// error holds its offset.
+///
+/// ```rust
+/// use unicode_normalization::{normalize_utf16, Form};
+///
+/// let mut out = Vec::new();
+/// normalize_utf16(&[0x61, 0x301], Form::Nfc, &mut out).unwrap();
+/// assert_eq!(out, [0xe1]);
+///
+/// let err = normalize_utf16(&[0x61, 0x301, 0xd800], Form::Nfc, &mut out).unwrap_err();
+/// assert_eq!(err.valid_up_to(), 2);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn normalize_utf16(input: &[u16], form: Form, output: &mut Vec<u16>)
+    -> Result<(), Utf16Error>
+{
+    let start = output.len();
+    let result = normalize(input, form, output, false);
+    if result.is_err() {
+        output.truncate(start);
+    }
+    result
+}
+
+/// Normalize potentially ill-formed UTF-16 (WTF-16) `input` into `form`,
+/// appending the result to `output`.
+///
+/// Lone surrogates are copied to the output unchanged.
+///
+/// ```rust
+/// use unicode_normalization::{normalize_wtf16, Form};
+///
+/// let mut out = Vec::new();
+/// normalize_wtf16(&[0x61, 0x301, 0xd800, 0x301], Form::Nfc, &mut out);
+/// assert_eq!(out, [0xe1, 0xd800, 0x301]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn normalize_wtf16(input: &[u16], form: Form, output: &mut Vec<u16>) {
+    let _ = normalize(input, form, output, true);
+}
+
+/// Quickly check if UTF-16 encoded `input` is in `form`, potentially
+/// returning `IsNormalized::Maybe` if further checks are necessary.
+///
+/// Returns an error if `input` contains a lone surrogate.
+pub fn is_normalized_utf16_quick(input: &[u16], form: Form) -> Result<IsNormalized, Utf16Error> {
+    quick_check_segments(input, form, false)
+}
+
+/// Authoritatively check if UTF-16 encoded `input` is in `form`.
+///
+/// Returns an error if `input` contains a lone surrogate.
+#[cfg(feature = "alloc")]
+pub fn is_normalized_utf16(input: &[u16], form: Form) -> Result<bool, Utf16Error> {
+    match is_normalized_utf16_quick(input, form)? {
+        IsNormalized::Yes => Ok(true),
+        IsNormalized::No => Ok(false),
+        IsNormalized::Maybe => {
+            Ok(form.is_normalized(Utf16CharIndices::new(input).map(|(_, ch)| ch)))
+        }
+    }
+}
+
+/// Quickly check if potentially ill-formed UTF-16 (WTF-16) `input` is in
+/// `form`, potentially returning `IsNormalized::Maybe` if further checks are
+/// necessary.
+pub fn is_normalized_wtf16_quick(input: &[u16], form: Form) -> IsNormalized {
+    match quick_check_segments(input, form, true) {
+        Ok(result) => result,
+        Err(_) => unreachable!(),
+    }
+}
+
+/// Authoritatively check if potentially ill-formed UTF-16 (WTF-16) `input`
+/// is in `form`.
+#[cfg(feature = "alloc")]
+pub fn is_normalized_wtf16(input: &[u16], form: Form) -> bool {
+    match is_normalized_wtf16_quick(input, form) {
+        IsNormalized::Yes => true,
+        IsNormalized::No => false,
+        IsNormalized::Maybe => {
+            let mut start = 0;
+            loop {
+                let iter = Utf16CharIndices::new(&input[start..]);
+                if !form.is_normalized(iter.clone().map(|(_, ch)| ch)) {
+                    return false;
+                }
+                match first_lone_surrogate(input, start) {
+                    Some(i) => start = i + 1,
+                    None => return true,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::{
+        EncodeUtf16,
+        Utf16Chars,
+        is_normalized_utf16,
+        is_normalized_utf16_quick,
+        is_normalized_wtf16,
+        is_normalized_wtf16_quick,
+        normalize_utf16,
+        normalize_wtf16,
+    };
+    use form::Form;
+    use normalization_tests::NORMALIZATION_TESTS;
+    use quick_check::IsNormalized;
+    use std::vec::Vec;
+    use UnicodeNormalization;
+
+    fn utf16(s: &str) -> Vec<u16> {
+        s.encode_utf16().collect()
+    }
+
+    #[test]
+    fn test_normalization_tests() {
+        for test in NORMALIZATION_TESTS {
+            for &(form, expected) in &[(Form::Nfc, test.nfc), (Form::Nfd, test.nfd),
+                                       (Form::Nfkc, test.nfkc), (Form::Nfkd, test.nfkd)] {
+                let mut out = Vec::new();
+                normalize_utf16(&utf16(test.source), form, &mut out).unwrap();
+                assert_eq!(out, utf16(expected));
+
+                let mut out = Vec::new();
+                normalize_wtf16(&utf16(test.source), form, &mut out);
+                assert_eq!(out, utf16(expected));
+
+                assert_eq!(is_normalized_utf16(&utf16(expected), form), Ok(true));
+                assert!(is_normalized_wtf16(&utf16(expected), form));
+            }
+            if test.nfc != test.nfd {
+                assert_eq!(is_normalized_utf16(&utf16(test.nfd), Form::Nfc), Ok(false));
+                assert!(!is_normalized_wtf16(&utf16(test.nfd), Form::Nfc));
+            }
+        }
+    }
+
+    #[test]
+    fn test_lone_surrogates() {
+        // "a", combining acute, lone high surrogate, combining acute, lone low
+        // surrogate, then "e" and a combining acute.
+        let input = [0x61, 0x301, 0xd800, 0x301, 0xdc00, 0x65, 0x301];
+
+        let mut out = vec![0x78];
+        let err = normalize_utf16(&input, Form::Nfc, &mut out).unwrap_err();
+        assert_eq!(err.valid_up_to(), 2);
+        assert_eq!(out, [0x78]);
+
+        normalize_wtf16(&input, Form::Nfc, &mut out);
+        assert_eq!(out, [0x78, 0xe1, 0xd800, 0x301, 0xdc00, 0xe9]);
+
+        let mut out = Vec::new();
+        normalize_wtf16(&[0xdc00, 0xd800], Form::Nfd, &mut out);
+        assert_eq!(out, [0xdc00, 0xd800]);
+
+        assert_eq!(is_normalized_utf16_quick(&input, Form::Nfd).unwrap_err().valid_up_to(), 2);
+        assert_eq!(is_normalized_utf16_quick(&[0x2126, 0xd800], Form::Nfc).unwrap_err().valid_up_to(), 1);
+        assert_eq!(is_normalized_wtf16_quick(&input, Form::Nfd), IsNormalized::Yes);
+        assert_eq!(is_normalized_wtf16_quick(&input, Form::Nfc), IsNormalized::Maybe);
+        assert!(!is_normalized_wtf16(&input, Form::Nfc));
+        assert!(is_normalized_wtf16(&[0xe1, 0xd800, 0x301, 0xdc00, 0xe9], Form::Nfc));
+    }
+
+    #[test]
+    fn test_adapters() {
+        for test in NORMALIZATION_TESTS {
+            let source = utf16(test.source);
+            let chars = || Utf16Chars::new(source.iter().cloned());
+            assert_eq!(EncodeUtf16::new(chars().nfc()).collect::<Vec<u16>>(), utf16(test.nfc));
+            assert_eq!(EncodeUtf16::new(chars().nfkd()).collect::<Vec<u16>>(), utf16(test.nfkd));
+        }
+
+        let chars: Vec<char> = Utf16Chars::new(vec![0xdc00, 0x61, 0xd800]).collect();
+        assert_eq!(chars, ['\u{fffd}', 'a', '\u{fffd}']);
+        let encoded = EncodeUtf16::new("a\u{1d15e}".chars());
+        assert_eq!(encoded.size_hint(), (2, Some(10)));
+        assert_eq!(encoded.collect::<Vec<u16>>(), [0x61, 0xd834, 0xdd5e]);
+    }
+}
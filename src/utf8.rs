@@ -0,0 +1,347 @@
+// Copyright 2019 The Rust Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution and at
+// http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Normalization of UTF-8 encoded bytes.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::char;
+use core::fmt;
+use core::str;
+#[cfg(feature = "std")]
+use std::error::Error;
+use form::Form;
+use quick_check::{IsNormalized, quick_check};
+#[cfg(feature = "alloc")]
+use quick_check::normalized_prefix_len;
+
+pub(crate) const REPLACEMENT_CHARACTER: char = '\u{FFFD}';
+
+/// An error returned when the input isn't valid UTF-8.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Utf8Error {
+    valid_up_to: usize,
+}
+
+impl Utf8Error {
+    #[cfg(feature = "std")]
+    pub(crate) fn new(valid_up_to: usize) -> Utf8Error {
+        Utf8Error { valid_up_to }
+    }
+
+    /// Returns the byte offset of the first invalid sequence in the input.
+    pub fn valid_up_to(&self) -> usize {
+        self.valid_up_to
+    }
+}
+
+impl fmt::Display for Utf8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid utf-8 sequence at byte offset {}", self.valid_up_to)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for Utf8Error {}
+
+/// The result of decoding a single character from UTF-8.
+pub(crate) enum Decoded {
+    /// A character and the length of its encoding.
+    Char(char, usize),
+    /// An ill-formed sequence of the given length, which is replaced by a
+    /// single U+FFFD in lossy decoding.
+    Invalid(usize),
+    /// The input ends partway through an otherwise well-formed sequence.
+    Incomplete,
+}
+
+/// Decode the first character of `bytes`, which must not be empty.
+#[inline]
+pub(crate) fn decode(bytes: &[u8]) -> Decoded {
+    let first = bytes[0];
+
+    // Lengths and bounds on the second byte of each well-formed sequence,
+    // from Table 3-7 of the Unicode Standard.
+    let (len, lo, hi) = match first {
+        0x00..=0x7f => return Decoded::Char(first as char, 1),
+        0xc2..=0xdf => (2, 0x80, 0xbf),
+        0xe0 => (3, 0xa0, 0xbf),
+        0xe1..=0xec | 0xee..=0xef => (3, 0x80, 0xbf),
+        0xed => (3, 0x80, 0x9f),
+        0xf0 => (4, 0x90, 0xbf),
+        0xf1..=0xf3 => (4, 0x80, 0xbf),
+        0xf4 => (4, 0x80, 0x8f),
+        _ => return Decoded::Invalid(1),
+    };
+
+    let mut c = (first as u32) & (0x7f >> len);
+    for i in 1..len {
+        let b = match bytes.get(i) {
+            Some(&b) => b,
+            None => return Decoded::Incomplete,
+        };
+        let (lo, hi) = if i == 1 { (lo, hi) } else { (0x80, 0xbf) };
+        if b < lo || b > hi {
+            return Decoded::Invalid(i);
+        }
+        c = (c << 6) | (b & 0x3f) as u32;
+    }
+    Decoded::Char(char::from_u32(c).unwrap_or(REPLACEMENT_CHARACTER), len)
+}
+
+/// Iterator over the characters of UTF-8 encoded bytes and their offsets.
+///
+/// In strict mode iteration stops at the first invalid sequence, while in
+/// lossy mode each maximal invalid subpart is replaced by U+FFFD.
+#[derive(Clone)]
+pub(crate) struct Utf8CharIndices<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    lossy: bool,
+}
+
+impl<'a> Utf8CharIndices<'a> {
+    pub(crate) fn strict(bytes: &'a [u8]) -> Self {
+        Utf8CharIndices { bytes, pos: 0, lossy: false }
+    }
+
+    #[cfg(feature = "alloc")]
+    pub(crate) fn lossy(bytes: &'a [u8]) -> Self {
+        Utf8CharIndices { bytes, pos: 0, lossy: true }
+    }
+
+    /// The offset of the next byte to decode. Once a strict iterator is
+    /// exhausted, this is either the length of the input or the offset of
+    /// its first invalid sequence.
+    pub(crate) fn offset(&self) -> usize {
+        self.pos
+    }
+}
+
+impl<'a> Iterator for Utf8CharIndices<'a> {
+    type Item = (usize, char);
+
+    #[inline]
+    fn next(&mut self) -> Option<(usize, char)> {
+        let start = self.pos;
+        if start == self.bytes.len() {
+            return None;
+        }
+        let (ch, len) = match decode(&self.bytes[start..]) {
+            Decoded::Char(ch, len) => (ch, len),
+            Decoded::Invalid(len) if self.lossy => (REPLACEMENT_CHARACTER, len),
+            Decoded::Incomplete if self.lossy => {
+                (REPLACEMENT_CHARACTER, self.bytes.len() - start)
+            }
+            _ => return None,
+        };
+        self.pos += len;
+        Some((start, ch))
+    }
+}
+
+/// Adapter for appending characters to a byte buffer as UTF-8.
+#[cfg(feature = "alloc")]
+pub(crate) struct Utf8Sink<'a>(pub(crate) &'a mut Vec<u8>);
+
+#[cfg(feature = "alloc")]
+impl<'a> Extend<char> for Utf8Sink<'a> {
+    #[inline]
+    fn extend<T: IntoIterator<Item=char>>(&mut self, iter: T) {
+        let mut buf = [0; 4];
+        for ch in iter {
+            self.0.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+        }
+    }
+}
+
+/// Find the end of the normalized prefix of `input` that can be copied over
+/// verbatim. This never extends past the first invalid sequence.
+#[cfg(feature = "alloc")]
+fn copyable_prefix_len(input: &[u8], form: Form) -> usize {
+    let mut iter = Utf8CharIndices::strict(input);
+    match normalized_prefix_len(iter.by_ref(), form.qc()) {
+        Some(n) => n,
+        None => iter.offset(),
+    }
+}
+
+fn validate(input: &[u8], from: usize) -> Result<(), Utf8Error> {
+    match str::from_utf8(&input[from..]) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(Utf8Error { valid_up_to: from + e.valid_up_to() }),
+    }
+}
+
+/// Normalize UTF-8 encoded `input` into `form`, appending the UTF-8 encoded
+/// result to `output`.
+///
+/// If `input` isn't valid UTF-8, `output` is left unchanged and the error
+/// holds the offset of the first invalid sequence.
+///
+/// ```rust
+/// use unicode_normalization::{normalize_utf8, Form};
+///
+/// let mut out = Vec::new();
+/// normalize_utf8(b"a\xcc\x81", Form::Nfc, &mut out).unwrap();
+/// assert_eq!(out, "\u{e1}".as_bytes());
+///
+/// let err = normalize_utf8(b"a\xcc\x81\xff", Form::Nfc, &mut out).unwrap_err();
+/// assert_eq!(err.valid_up_to(), 3);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn normalize_utf8(input: &[u8], form: Form, output: &mut Vec<u8>) -> Result<(), Utf8Error> {
+    let start = output.len();
+    let n = copyable_prefix_len(input, form);
+    output.extend_from_slice(&input[..n]);
+
+    let rest = &input[n..];
+    let mut iter = Utf8CharIndices::strict(rest);
+    form.extend(iter.by_ref().map(|(_, ch)| ch), &mut Utf8Sink(output));
+    if iter.offset() < rest.len() {
+        output.truncate(start);
+        return Err(Utf8Error { valid_up_to: n + iter.offset() });
+    }
+    Ok(())
+}
+
+/// Normalize UTF-8 encoded `input` into `form`, appending the UTF-8 encoded
+/// result to `output` and replacing invalid sequences with U+FFFD.
+///
+/// Invalid sequences are replaced the same way as by
+/// `String::from_utf8_lossy`.
+#[cfg(feature = "alloc")]
+pub fn normalize_utf8_lossy(input: &[u8], form: Form, output: &mut Vec<u8>) {
+    let n = copyable_prefix_len(input, form);
+    output.extend_from_slice(&input[..n]);
+
+    let iter = Utf8CharIndices::lossy(&input[n..]);
+    form.extend(iter.map(|(_, ch)| ch), &mut Utf8Sink(output));
+}
+
+/// Quickly check if UTF-8 encoded `input` is in `form`, potentially
+/// returning `IsNormalized::Maybe` if further checks are necessary.
+///
+/// Returns an error if `input` isn't valid UTF-8.
+pub fn is_normalized_utf8_quick(input: &[u8], form: Form) -> Result<IsNormalized, Utf8Error> {
+    let mut iter = Utf8CharIndices::strict(input);
+    let result = quick_check(iter.by_ref().map(|(_, ch)| ch), form.qc(), false);
+    validate(input, iter.offset())?;
+    Ok(result)
+}
+
+/// Authoritatively check if UTF-8 encoded `input` is in `form`.
+///
+/// Returns an error if `input` isn't valid UTF-8.
+#[cfg(feature = "alloc")]
+pub fn is_normalized_utf8(input: &[u8], form: Form) -> Result<bool, Utf8Error> {
+    match is_normalized_utf8_quick(input, form)? {
+        IsNormalized::Yes => Ok(true),
+        IsNormalized::No => Ok(false),
+        IsNormalized::Maybe => {
+            Ok(form.is_normalized(Utf8CharIndices::strict(input).map(|(_, ch)| ch)))
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::{
+        Utf8CharIndices,
+        is_normalized_utf8,
+        is_normalized_utf8_quick,
+        normalize_utf8,
+        normalize_utf8_lossy,
+    };
+    use form::Form;
+    use normalization_tests::NORMALIZATION_TESTS;
+    use quick_check::IsNormalized;
+    use std::string::String;
+    use std::vec::Vec;
+    use UnicodeNormalization;
+
+    const INVALID: &[&[u8]] = &[
+        b"\xff",
+        b"a\xcc",
+        b"a\xcc\x81\xe0\x80z",
+        b"\xed\xa0\x80\xcc\x81",
+        b"\xf4\x90\x80\x80",
+        b"\xc0\xaf\xe2\x84",
+        b"\xf0\x9f\x98",
+        b"\xe1\x84\x80\xff\xe1\x85\xa1",
+    ];
+
+    #[test]
+    fn test_decode_matches_std() {
+        for &bytes in INVALID {
+            let lossy = String::from_utf8_lossy(bytes);
+            let decoded: String = Utf8CharIndices::lossy(bytes).map(|(_, ch)| ch).collect();
+            assert_eq!(decoded, lossy);
+        }
+        let s = "a\u{e1}\u{2126}\u{1f600}";
+        let indices: Vec<_> = Utf8CharIndices::strict(s.as_bytes()).collect();
+        assert_eq!(indices, s.char_indices().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_normalization_tests() {
+        for test in NORMALIZATION_TESTS {
+            for &(form, expected) in &[(Form::Nfc, test.nfc), (Form::Nfd, test.nfd),
+                                       (Form::Nfkc, test.nfkc), (Form::Nfkd, test.nfkd)] {
+                let mut out = Vec::new();
+                normalize_utf8(test.source.as_bytes(), form, &mut out).unwrap();
+                assert_eq!(out, expected.as_bytes());
+
+                assert_eq!(is_normalized_utf8(expected.as_bytes(), form), Ok(true));
+            }
+            if test.nfc != test.nfd {
+                assert_eq!(is_normalized_utf8(test.nfd.as_bytes(), Form::Nfc), Ok(false));
+            }
+        }
+    }
+
+    #[test]
+    fn test_strict() {
+        let mut out = b"prefix".to_vec();
+        for &bytes in INVALID {
+            let valid_up_to = ::std::str::from_utf8(bytes).unwrap_err().valid_up_to();
+            let err = normalize_utf8(bytes, Form::Nfc, &mut out).unwrap_err();
+            assert_eq!(err.valid_up_to(), valid_up_to);
+            assert_eq!(out, b"prefix");
+
+            let err = is_normalized_utf8_quick(bytes, Form::Nfd).unwrap_err();
+            assert_eq!(err.valid_up_to(), valid_up_to);
+        }
+
+        // The error is reported even if the result is known before reaching it.
+        let err = is_normalized_utf8_quick(b"\xe2\x84\xa6\xff", Form::Nfc).unwrap_err();
+        assert_eq!(err.valid_up_to(), 3);
+        assert_eq!(is_normalized_utf8_quick(b"\xe2\x84\xa6", Form::Nfc), Ok(IsNormalized::No));
+    }
+
+    #[test]
+    fn test_lossy() {
+        for &bytes in INVALID {
+            for &form in &[Form::Nfc, Form::Nfd, Form::Nfkc, Form::Nfkd] {
+                let lossy = String::from_utf8_lossy(bytes);
+                let mut expected = Vec::new();
+                normalize_utf8(lossy.as_bytes(), form, &mut expected).unwrap();
+
+                let mut out = Vec::new();
+                normalize_utf8_lossy(bytes, form, &mut out);
+                assert_eq!(out, expected);
+            }
+        }
+
+        let mut out = Vec::new();
+        normalize_utf8_lossy(b"\xe1\x84\x80\xff\xe1\x85\xa1a\xcc\x81", Form::Nfc, &mut out);
+        assert_eq!(out, "\u{1100}\u{fffd}\u{1161}\u{e1}".nfc().collect::<String>().as_bytes());
+    }
+}